@@ -0,0 +1,54 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use helloworld::{apply_window, detect_note, stft, Window};
+use std::f32::consts::PI;
+use std::hint::black_box;
+
+const SAMPLE_RATE: u32 = 44_100;
+
+// A 440 Hz sine with a little of its octave, `len` samples long
+fn tone(len: usize) -> Vec<f32> {
+    (0..len)
+        .map(|n| {
+            let t = n as f32 / SAMPLE_RATE as f32;
+            0.5 * (2.0 * PI * 440.0 * t).sin() + 0.1 * (2.0 * PI * 880.0 * t).sin()
+        })
+        .collect()
+}
+
+// The whole pipeline on inputs whose downsampled excerpts need FFTs of 1024 to 16384 points
+fn bench_detect_note(c: &mut Criterion) {
+    let mut group = c.benchmark_group("detect_note");
+    for fft_size in [1024, 4096, 16_384] {
+        let samples = tone(fft_size * helloworld::DEFAULT_DOWNSAMPLE);
+        group.bench_with_input(BenchmarkId::from_parameter(fft_size), &samples, |b, samples| {
+            b.iter(|| detect_note(black_box(samples), SAMPLE_RATE))
+        });
+    }
+    group.finish();
+}
+
+fn bench_window(c: &mut Criterion) {
+    let mut group = c.benchmark_group("apply_window");
+    for size in [1024, 4096, 16_384] {
+        let samples = tone(size);
+        group.bench_with_input(BenchmarkId::from_parameter(size), &samples, |b, samples| {
+            b.iter(|| apply_window(black_box(samples), Window::Hann))
+        });
+    }
+    group.finish();
+}
+
+// A single STFT frame covering the whole input is one windowed real FFT
+fn bench_fft(c: &mut Criterion) {
+    let mut group = c.benchmark_group("fft");
+    for size in [1024, 4096, 16_384] {
+        let samples = tone(size);
+        group.bench_with_input(BenchmarkId::from_parameter(size), &samples, |b, samples| {
+            b.iter(|| stft(black_box(samples), size, size))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_detect_note, bench_window, bench_fft);
+criterion_main!(benches);
@@ -0,0 +1,123 @@
+use crate::NoteEvent;
+
+// ABC pitch letters of the twelve pitch classes, sharps marked with `^`
+const ABC_NAMES: [&str; 12] = ["C", "^C", "D", "^D", "E", "F", "^F", "G", "^G", "A", "^A", "B"];
+
+// Eighth notes per 4/4 bar, and bars per line of the tune body
+const EIGHTHS_PER_BAR: usize = 8;
+const BARS_PER_LINE: usize = 4;
+
+/// Default tempo in beats (quarter notes) per minute for [`to_abc`].
+pub const DEFAULT_BPM: f32 = 120.0;
+
+/// Write note events as a minimal ABC tune titled `title`, in 4/4 with quarter-note beats at
+/// `bpm`.
+///
+/// Onsets and releases are rounded to the nearest eighth note, so every note lasts at least an
+/// eighth; gaps between notes become rests and notes crossing a bar line are tied across it.
+/// Overlapping notes are played one after the other. MIDI 60 is middle C (`C`), with octaves
+/// marked by lowercase letters, `'` and `,`.
+pub fn to_abc(notes: &[NoteEvent], bpm: f32, title: &str) -> String {
+    let eighths_per_second = bpm / 60.0 * 2.0;
+    let to_eighths = |seconds: f32| (seconds.max(0.0) * eighths_per_second).round() as usize;
+
+    // A header field ends at the line break, so the title can't hold one
+    let title = title.replace(['\r', '\n'], " ");
+    let mut abc = format!("X:1\nT:{}\nM:4/4\nL:1/8\nQ:1/4={}\nK:C\n", title, bpm.round());
+    let mut bar = Bar::default();
+    for note in notes {
+        let start = to_eighths(note.start_sec).max(bar.position);
+        let end = to_eighths(note.end_sec).max(start + 1);
+        bar.push(&mut abc, "z", start - bar.position);
+        bar.push(&mut abc, &abc_pitch(note.midi), end - start);
+    }
+    if bar.position > 0 {
+        // Replace the separator after the last token with the final bar line
+        abc.truncate(abc.trim_end_matches([' ', '|', '\n']).len());
+        abc.push_str(" |]\n");
+    }
+    abc
+}
+
+// ABC name of a MIDI note number, e.g. `^c'` for 73
+fn abc_pitch(midi: i32) -> String {
+    let name = ABC_NAMES[midi.rem_euclid(12) as usize];
+    match midi.div_euclid(12) - 1 {
+        octave if octave <= 4 => format!("{}{}", name, ",".repeat((4 - octave) as usize)),
+        octave => format!("{}{}", name.to_lowercase(), "'".repeat((octave - 5) as usize)),
+    }
+}
+
+// Tracks the position in eighths while writing tokens, inserting bar lines and ties
+#[derive(Default)]
+struct Bar {
+    position: usize,
+    // Notes sharpened earlier in the current bar, whose sharp carries on until the bar line
+    sharpened: Vec<String>,
+}
+
+impl Bar {
+    // Write `pitch` (or `z` for a rest) lasting `length` eighths, split at bar lines
+    fn push(&mut self, abc: &mut String, pitch: &str, mut length: usize) {
+        while length > 0 {
+            let part = length.min(EIGHTHS_PER_BAR - self.position % EIGHTHS_PER_BAR);
+            match pitch.strip_prefix('^') {
+                Some(natural) if !self.sharpened.iter().any(|sharp| sharp == natural) => {
+                    self.sharpened.push(natural.to_string());
+                }
+                None if self.sharpened.iter().any(|sharp| sharp == pitch) => {
+                    self.sharpened.retain(|sharp| sharp != pitch);
+                    abc.push('=');
+                }
+                _ => {}
+            }
+            abc.push_str(pitch);
+            if part > 1 {
+                abc.push_str(&part.to_string());
+            }
+            length -= part;
+            self.position += part;
+            if length > 0 && pitch != "z" {
+                abc.push('-');
+            }
+            if self.position.is_multiple_of(EIGHTHS_PER_BAR) {
+                self.sharpened.clear();
+                let bars = self.position / EIGHTHS_PER_BAR;
+                abc.push_str(if bars.is_multiple_of(BARS_PER_LINE) { "|\n" } else { " | " });
+            } else {
+                abc.push(' ');
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::note;
+
+    #[test]
+    fn names_pitches_across_octaves() {
+        let names: Vec<String> = [48, 60, 61, 71, 72, 84, 85].iter().map(|&midi| abc_pitch(midi)).collect();
+        assert_eq!(names, ["C,", "C", "^C", "B", "c", "c'", "^c'"]);
+    }
+
+    #[test]
+    fn writes_a_melody_with_rests_and_ties() {
+        // At 120 BPM an eighth lasts 0.25 s
+        let notes = [note(60, 0.0, 0.5), note(62, 0.5, 0.75), note(64, 1.0, 2.5), note(67, 2.5, 2.7)];
+        let abc = to_abc(&notes, 120.0, "Transcription");
+        assert!(abc.starts_with("X:1\nT:Transcription\nM:4/4\nL:1/8\nQ:1/4=120\nK:C\n"), "{abc}");
+        assert!(abc.ends_with("K:C\nC2 D z E4- | E2 G |]\n"), "{abc}");
+        let bar = to_abc(&[note(60, 0.0, 2.0), note(60, 2.0, 4.0)], 120.0, "Bars");
+        assert!(bar.ends_with("K:C\nC8 | C8 |]\n"), "{bar}");
+        assert!(bar.contains("\nT:Bars\n"), "{bar}");
+    }
+
+    #[test]
+    fn cancels_sharps_within_a_bar() {
+        let notes = [note(61, 0.0, 0.5), note(60, 0.5, 1.0), note(61, 1.0, 2.5), note(60, 2.5, 3.0)];
+        let abc = to_abc(&notes, 120.0, "Transcription");
+        assert!(abc.ends_with("K:C\n^C2 =C2 ^C4- | ^C2 =C2 |]\n"), "{abc}");
+    }
+}
@@ -0,0 +1,464 @@
+use crate::plan::FftCache;
+use crate::stft::{pitch_track_with, stft_with};
+use crate::{
+    analyze_with, frame_rms, segment_notes, Analysis, Emphasis, Error, Interpolation, Method, NoteEvent, NoteResult,
+    Notation, PitchEstimate, Settings, Window, A4_HZ,
+};
+use serde::Serialize;
+use std::ops::RangeInclusive;
+
+/// One entry point for the whole pipeline, configured with a builder:
+///
+/// ```no_run
+/// # use helloworld::{Method, Window, WavAnalyzer};
+/// # let (samples, sample_rate) = helloworld::load_wav("a.wav".as_ref()).unwrap();
+/// let note = WavAnalyzer::new()
+///     .downsample(8)
+///     .window(Window::Hann)
+///     .tuning(440.0)
+///     .method(Method::Fft)
+///     .detect(&samples, sample_rate)?;
+/// # Ok::<(), helloworld::Error>(())
+/// ```
+///
+/// FFT plans are cached per size, so an analyzer reused across many recordings or frames, even
+/// from several threads, plans each size only once.
+#[derive(Debug, Clone)]
+pub struct WavAnalyzer {
+    /// Knobs passed to [`analyze`](crate::analyze).
+    pub settings: Settings,
+    /// Reference frequency of A4 in Hz.
+    pub tuning: f32,
+    /// Convention for naming the detected note.
+    pub notation: Notation,
+    /// Semitones added to the detected note before naming it, e.g. 2 to read for a B-flat
+    /// instrument. The measured frequency and cents are left as they are.
+    pub transpose: i32,
+    plans: FftCache,
+}
+
+/// What [`WavAnalyzer::transcribe`] finds in a recording, for callers such as a GUI to display
+/// however they like.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Transcription {
+    /// The detected note of the analyzed excerpt, as [`WavAnalyzer::detect`] gives it.
+    pub note: Option<NoteResult>,
+    /// The notes of the whole recording, as [`WavAnalyzer::melody`] gives them, transposed by
+    /// [`WavAnalyzer::transpose`] semitones.
+    pub notes: Vec<NoteEvent>,
+}
+
+impl Default for WavAnalyzer {
+    /// The default [`Settings`], with A4 at 440 Hz, English sharp note names and no transposition.
+    fn default() -> Self {
+        WavAnalyzer {
+            settings: Settings::default(),
+            tuning: A4_HZ,
+            notation: Notation::default(),
+            transpose: 0,
+            plans: FftCache::default(),
+        }
+    }
+}
+
+impl WavAnalyzer {
+    /// An analyzer with the default configuration.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Pitch-detection algorithm.
+    pub fn method(mut self, method: Method) -> Self {
+        self.settings.method = method;
+        self
+    }
+
+    /// Integer decimation factor applied before analysis.
+    pub fn downsample(mut self, factor: usize) -> Self {
+        self.settings.downsample = factor;
+        self
+    }
+
+    /// FFT length, at least 2, to which each frame is zero-padded or cut, in place of the next
+    /// power of two above its length.
+    pub fn fft_size(mut self, size: usize) -> Self {
+        self.settings.fft_size = Some(size);
+        self
+    }
+
+    /// Rate in Hz to resample the recording to before analysis, in place of decimating it.
+    pub fn target_rate(mut self, hz: u32) -> Self {
+        self.settings.target_rate = Some(hz);
+        self
+    }
+
+    /// Window applied before the FFT.
+    pub fn window(mut self, window: Window) -> Self {
+        self.settings.window = window;
+        self
+    }
+
+    /// Offset of the analyzed excerpt from the start of the recording, in seconds.
+    pub fn start(mut self, seconds: f32) -> Self {
+        self.settings.start_sec = seconds;
+        self
+    }
+
+    /// Length of the analyzed excerpt in seconds.
+    pub fn duration(mut self, seconds: f32) -> Self {
+        self.settings.duration_sec = seconds;
+        self
+    }
+
+    /// Lowest frequency the peak search considers, in Hz.
+    pub fn min_freq(mut self, hz: f32) -> Self {
+        self.settings.min_freq = hz;
+        self
+    }
+
+    /// Highest frequency the peak search considers and a note may be reported for, in Hz.
+    pub fn max_freq(mut self, hz: f32) -> Self {
+        self.settings.max_freq = hz;
+        self
+    }
+
+    /// Whether to subtract the excerpt's mean before analysis.
+    pub fn remove_dc(mut self, remove_dc: bool) -> Self {
+        self.settings.remove_dc = remove_dc;
+        self
+    }
+
+    /// How the FFT peak is refined between bins.
+    pub fn interpolation(mut self, interpolation: Interpolation) -> Self {
+        self.settings.interpolation = interpolation;
+        self
+    }
+
+    /// First-order emphasis filter applied before analysis, or `None` for no filtering.
+    pub fn emphasis(mut self, emphasis: Option<Emphasis>) -> Self {
+        self.settings.emphasis = emphasis;
+        self
+    }
+
+    /// Whether to move the FFT peak down an octave while half its frequency also carries energy.
+    pub fn fix_octave(mut self, fix_octave: bool) -> Self {
+        self.settings.fix_octave = fix_octave;
+        self
+    }
+
+    /// Whether to scale the excerpt to a peak of 1.0 before analysis.
+    pub fn normalize(mut self, normalize: bool) -> Self {
+        self.settings.normalize = normalize;
+        self
+    }
+
+    /// Whether to start the excerpt at the first sound louder than the silence threshold.
+    pub fn trim_silence(mut self, trim_silence: bool) -> Self {
+        self.settings.trim_silence = trim_silence;
+        self
+    }
+
+    /// Level in dBFS that counts as sound when trimming silence.
+    pub fn silence_threshold(mut self, dbfs: f32) -> Self {
+        self.settings.silence_dbfs = dbfs;
+        self
+    }
+
+    /// Number of half-overlapping frames whose magnitude spectra are averaged before the peak
+    /// search, 1 for a single FFT over the whole excerpt.
+    pub fn average_frames(mut self, frames: usize) -> Self {
+        self.settings.average_frames = frames;
+        self
+    }
+
+    /// Whether to read the spectrum above [`MULTIRES_CROSSOVER_HZ`](crate::MULTIRES_CROSSOVER_HZ)
+    /// from frames [`MULTIRES_RATIO`](crate::MULTIRES_RATIO) times shorter.
+    pub fn multires(mut self, multires: bool) -> Self {
+        self.settings.multires = multires;
+        self
+    }
+
+    /// Width in bins of the moving average the peak search runs on, 1 for none.
+    pub fn smooth_spectrum(mut self, width: usize) -> Self {
+        self.settings.smooth_spectrum = width;
+        self
+    }
+
+    /// Frequencies in Hz outside which the spectrum is set to zero before any peak is picked.
+    pub fn band(mut self, band: RangeInclusive<f32>) -> Self {
+        self.settings.band = Some(band);
+        self
+    }
+
+    /// Whether to refine the detected frequency from the phase advance between two frames (see
+    /// [`instantaneous_frequency`](crate::instantaneous_frequency)).
+    pub fn precise(mut self, precise: bool) -> Self {
+        self.settings.precise = precise;
+        self
+    }
+
+    /// Reference frequency of A4 in Hz.
+    pub fn tuning(mut self, a4_hz: f32) -> Self {
+        self.tuning = a4_hz;
+        self
+    }
+
+    /// Convention for naming the detected note.
+    pub fn notation(mut self, notation: Notation) -> Self {
+        self.notation = notation;
+        self
+    }
+
+    /// Semitones added to the detected note before naming it; negative values transpose down.
+    pub fn transpose(mut self, semitones: i32) -> Self {
+        self.transpose = semitones;
+        self
+    }
+
+    /// Run the spectral analysis on mono samples (see [`analyze`](crate::analyze)).
+    pub fn analyze(&self, samples: &[f32], sample_rate: u32) -> Result<Analysis, Error> {
+        analyze_with(samples, sample_rate, &self.settings, &self.plans)
+    }
+
+    /// Short-time Fourier transform of `samples` (see [`stft`](crate::stft)).
+    pub fn stft(&self, samples: &[f32], frame: usize, hop: usize) -> Vec<Vec<f32>> {
+        stft_with(samples, frame, hop, &self.plans)
+    }
+
+    /// Pitch of each STFT frame (see [`pitch_track`](crate::pitch_track)), searching this
+    /// analyzer's frequency range and refining and octave-correcting each frame's peak the way
+    /// [`analyze`](Self::analyze) does.
+    pub fn pitch_track(&self, frames: &[Vec<f32>], sample_rate: f32) -> Vec<Option<PitchEstimate>> {
+        pitch_track_with(frames, sample_rate, &self.settings)
+    }
+
+    /// Transcribe a monophonic recording: the dominant note of each `frame`-sample STFT frame,
+    /// every `hop` samples, grouped into note events (see [`segment_notes`]).
+    ///
+    /// The recording is downsampled or resampled as the settings say, but isn't cut to the
+    /// analyzed excerpt. Note times are in seconds from its start.
+    pub fn melody(&self, samples: &[f32], sample_rate: u32, frame: usize, hop: usize) -> Vec<NoteEvent> {
+        let (samples, sample_rate) = self.settings.convert_rate(samples, sample_rate);
+        let pitches: Vec<Option<f32>> = self.pitch_track(&self.stft(&samples, frame, hop), sample_rate)
+            .into_iter()
+            .map(|estimate| estimate.map(|estimate| estimate.frequency))
+            .collect();
+        segment_notes(&pitches, &frame_rms(&samples, frame, hop), hop, sample_rate, self.tuning)
+    }
+
+    /// The detected note in mono samples, transposed by [`transpose`](Self::transpose) semitones,
+    /// or `None` if it falls outside the searched range.
+    pub fn detect(&self, samples: &[f32], sample_rate: u32) -> Result<Option<NoteResult>, Error> {
+        let note = self.analyze(samples, sample_rate)?.note(self.tuning, self.notation);
+        Ok(note.map(|note| note.transposed(self.transpose, self.notation)))
+    }
+
+    /// The detected note and the melody of mono samples together, the melody from `frame`-sample
+    /// STFT frames every `hop` samples.
+    ///
+    /// Like every analysis in this crate, this only computes: nothing is printed or written, and
+    /// failures are returned rather than reported.
+    pub fn transcribe(
+        &self,
+        samples: &[f32],
+        sample_rate: u32,
+        frame: usize,
+        hop: usize,
+    ) -> Result<Transcription, Error> {
+        Ok(Transcription {
+            note: self.detect(samples, sample_rate)?,
+            notes: self.melody(samples, sample_rate, frame, hop)
+                .iter()
+                .map(|note| note.transposed(self.transpose))
+                .collect(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{mix, sine, Noise};
+
+    #[test]
+    fn cached_plans_give_the_same_spectra() {
+        let samples: Vec<f32> = (0..4096).map(|n| (n as f32 * 0.05).sin()).collect();
+        let analyzer = WavAnalyzer::new();
+        let first = analyzer.stft(&samples, 1024, 256);
+        assert_eq!(analyzer.stft(&samples, 1024, 256), first);
+        assert_eq!(crate::stft(&samples, 1024, 256), first);
+    }
+
+    #[test]
+    fn search_range_follows_max_freq_at_any_decimation() {
+        // 8 kHz lies beyond the lowest quarter of the undecimated spectrum, where a search over a
+        // fixed fraction of the bins would stop
+        let tone = sine(8000.0, 44_100, 44_100);
+        let analysis = WavAnalyzer::new().downsample(1).max_freq(10_000.0).analyze(&tone, 44_100).unwrap();
+        assert!((analysis.frequency.unwrap() - 8000.0).abs() < 1.0);
+        assert_eq!(*analysis.search_bins().end(), (10_000.0 * analysis.fft_size as f32 / 44_100.0) as usize);
+    }
+
+    #[test]
+    fn names_the_note_against_the_tuning() {
+        // 415 Hz, the A of baroque pitch, a little under G#4 at A4 = 440 Hz
+        let tone = sine(415.0, 44_100, 44_100);
+        let concert = WavAnalyzer::new().detect(&tone, 44_100).unwrap().unwrap();
+        assert_eq!(concert.note, "G#4");
+        let baroque = WavAnalyzer::new().tuning(415.0).precise(true).detect(&tone, 44_100).unwrap().unwrap();
+        assert_eq!((baroque.note.as_str(), baroque.midi), ("A4", 69));
+        assert!(baroque.cents.abs() < 0.1, "{} cents", baroque.cents);
+    }
+
+    #[test]
+    fn analyzes_a_low_rate_file_at_a_higher_target_rate() {
+        // E4 recorded at 8 kHz
+        let tone = sine(329.63, 16_000, 8000);
+        let analysis = WavAnalyzer::new().target_rate(22_050).analyze(&tone, 8000).unwrap();
+        assert_eq!(analysis.sample_rate, 22_050.0);
+        let note = analysis.note(crate::A4_HZ, Notation::default()).unwrap();
+        assert_eq!(note.note, "E4");
+        assert!(note.cents.abs() < 1.0, "{} cents", note.cents);
+        assert!(matches!(WavAnalyzer::new().target_rate(0).analyze(&tone, 8000), Err(Error::ZeroTargetRate)));
+    }
+
+    #[test]
+    fn transcribes_a_melody_in_the_searched_range() {
+        // C4, E4 and G4 for half a second each, over a louder 50 Hz hum
+        let sample_rate = 8000;
+        let half = sample_rate as usize / 2;
+        let hum = sine(50.0, 3 * half, sample_rate);
+        let melody = [261.63, 329.63, 392.0]
+            .iter()
+            .flat_map(|&frequency| mix(&[(frequency, 0.3)], half, sample_rate));
+        let samples: Vec<f32> = melody.zip(&hum).map(|(note, hum)| note + 0.5 * hum).collect();
+        let analyzer = WavAnalyzer::new().downsample(1);
+        assert!(analyzer.melody(&samples, sample_rate, 1024, 256).iter().all(|note| note.midi < 40));
+
+        let notes = analyzer.min_freq(100.0).melody(&samples, sample_rate, 1024, 256);
+        let midis: Vec<i32> = notes.iter().map(|note| note.midi).collect();
+        assert_eq!(midis, [60, 64, 67]);
+        assert!((notes[1].start_sec - 0.5).abs() < 0.1, "E4 starts at {} s", notes[1].start_sec);
+
+        // Read for a B-flat instrument, the whole transcription moves up a tone
+        let transposed = WavAnalyzer::new().downsample(1).min_freq(100.0).transpose(2);
+        let transcription = transposed.transcribe(&samples, sample_rate, 1024, 256).unwrap();
+        let midis: Vec<i32> = transcription.notes.iter().map(|note| note.midi).collect();
+        assert_eq!(midis, [62, 66, 69]);
+        assert_eq!(transcription.note, transposed.detect(&samples, sample_rate).unwrap());
+        assert!(WavAnalyzer::new().transcribe(&[], sample_rate, 1024, 256).is_err());
+    }
+
+    #[test]
+    fn transposes_only_the_name_of_the_note() {
+        // C4, read for a B-flat instrument
+        let tone = sine(261.63, 16_000, 8000);
+        let concert = WavAnalyzer::new().detect(&tone, 8000).unwrap().unwrap();
+        let note = WavAnalyzer::new().transpose(2).detect(&tone, 8000).unwrap().unwrap();
+        assert_eq!((note.note.as_str(), note.midi), ("D4", 62));
+        assert_eq!((note.frequency, note.cents), (concert.frequency, concert.cents));
+        assert_eq!(WavAnalyzer::new().transpose(-1).detect(&tone, 8000).unwrap().unwrap().note, "B3");
+    }
+
+    #[test]
+    fn precise_reads_a_test_tone_to_a_tenth_of_a_hertz() {
+        // Decimated by 8 to 5512.5 Hz, which bins cut into 0.34 Hz steps
+        let tone = sine(1000.0, 88_200, 44_100);
+        let analysis = WavAnalyzer::new().precise(true).analyze(&tone, 44_100).unwrap();
+        assert_eq!(analysis.sample_rate, 5512.5);
+        let frequency = analysis.frequency.unwrap();
+        assert!((frequency - 1000.0).abs() < 0.01, "{} Hz", frequency);
+
+        // Resampled to whole-hertz rates instead
+        for rate in [5512, 8000] {
+            let analyzer = WavAnalyzer::new().precise(true).target_rate(rate);
+            let frequency = analyzer.analyze(&tone, 44_100).unwrap().frequency.unwrap();
+            assert!((frequency - 1000.0).abs() < 0.01, "at {} Hz: {} Hz", rate, frequency);
+        }
+    }
+
+    #[test]
+    fn fft_size_sets_the_bin_spacing_whatever_the_length() {
+        let tone = |len: usize| sine(440.0, len, 8000);
+        let analyzer = WavAnalyzer::new().downsample(1).fft_size(8192);
+        for len in [3000, 12_000] {
+            let analysis = analyzer.analyze(&tone(len), 8000).unwrap();
+            assert_eq!((analysis.fft_size, analysis.window_size), (8192, len.min(8192)));
+            assert!((analysis.frequency.unwrap() - 440.0).abs() < 1.0);
+        }
+        let result = WavAnalyzer::new().fft_size(1).analyze(&tone(3000), 8000);
+        assert!(matches!(result, Err(Error::FftSizeTooSmall { size: 1 })));
+    }
+
+    #[test]
+    fn magnitudes_dont_depend_on_the_window() {
+        // A 0.5-amplitude tone centered on bin 1000, whose peak should read 0.5 * N / 2 = 2048
+        let tone = mix(&[(1000.0, 0.5)], 8192, 8192);
+        for window in [Window::Rectangular, Window::Hann, Window::BlackmanHarris, Window::FlatTop] {
+            let analysis = WavAnalyzer::new().downsample(1).window(window).analyze(&tone, 8192).unwrap();
+            assert_eq!(analysis.max_index, Some(1000));
+            let magnitude = analysis.magnitudes[1000];
+            assert!((magnitude - 2048.0).abs() < 2.0, "{:?} peak {}", window, magnitude);
+        }
+
+        // A Gaussian needs a width to have a shape at all
+        for sigma in [0.0, -0.4, f32::NAN, f32::INFINITY] {
+            let result = WavAnalyzer::new().window(Window::Gaussian(sigma)).analyze(&tone, 8192);
+            assert!(matches!(result, Err(Error::InvalidWindowSigma { .. })), "{sigma}: {result:?}");
+        }
+    }
+
+    #[test]
+    fn multires_sharpens_a_wavering_overtone() {
+        // A steady 40 Hz bass under a 3 kHz overtone whose pitch wanders randomly by a few cents
+        let sample_rate = 16_000;
+        let mut noise = Noise::new();
+        let mut cents = 0.0f32;
+        let mut phase = 0.0;
+        let samples: Vec<f32> = sine(40.0, 2 * sample_rate as usize, sample_rate)
+            .iter()
+            .map(|bass| {
+                cents = 0.999 * cents + 0.5 * noise.next();
+                phase += 2.0 * std::f32::consts::PI * 3000.0 * 2f32.powf(cents / 1200.0) / sample_rate as f32;
+                0.3 * bass + 0.3 * phase.sin()
+            })
+            .collect();
+        let peaks = |analyzer: WavAnalyzer| {
+            let analysis = analyzer.downsample(1).max_freq(5000.0).analyze(&samples, sample_rate).unwrap();
+            analysis.top_peaks(3, A4_HZ, Notation::default())
+        };
+
+        // One long frame resolves the bass but shreds the overtone into several weaker peaks
+        let single = peaks(WavAnalyzer::new());
+        assert!((single[0].frequency - 40.0).abs() < 0.1);
+        assert!(single[1].magnitude < 0.6 * single[0].magnitude);
+
+        // Short frames gather it back into one peak as strong as the equally loud bass
+        let multires = peaks(WavAnalyzer::new().multires(true));
+        assert!((multires[0].frequency - 40.0).abs() < 0.1);
+        assert!((multires[1].frequency - 3000.0).abs() < 5.0, "overtone at {} Hz", multires[1].frequency);
+        assert!(multires[1].magnitude > 0.8 * multires[0].magnitude);
+        assert!(multires[2].magnitude < 0.1 * multires[0].magnitude);
+    }
+
+    #[test]
+    fn averaging_frames_steadies_a_noisy_tone() {
+        // A quiet 440 Hz tone in loud white noise, from a fixed linear congruential generator
+        let mut noise = Noise::new();
+        let samples: Vec<f32> =
+            mix(&[(440.0, 0.05)], 44_100, 44_100).iter().map(|tone| tone + noise.next()).collect();
+        let single = WavAnalyzer::new().analyze(&samples, 44_100).unwrap();
+        let averaged = WavAnalyzer::new().average_frames(8).analyze(&samples, 44_100).unwrap();
+        assert_eq!(averaged.window_size, 2 * single.window_size / 9);
+        let frequency = averaged.frequency.unwrap();
+        assert!((frequency - 440.0).abs() < 5.0, "got {frequency} Hz");
+
+        // The noise floor's spread relative to its level, over the bins above the tone
+        let spread = |magnitudes: &[f32]| {
+            let floor = &magnitudes[magnitudes.len() / 2..];
+            let mean = floor.iter().sum::<f32>() / floor.len() as f32;
+            (floor.iter().map(|m| (m - mean).powi(2)).sum::<f32>() / floor.len() as f32).sqrt() / mean
+        };
+        assert!(spread(&averaged.magnitudes) < 0.5 * spread(&single.magnitudes));
+    }
+}
@@ -0,0 +1,130 @@
+use crate::plan::FftCache;
+use crate::{parabolic_offset, Window};
+use realfft::num_complex::Complex;
+
+// Quefrencies searched for the cepstral peak at most, as fundamentals in Hz
+const CEPSTRUM_MIN_HZ: f32 = 50.0;
+const CEPSTRUM_MAX_HZ: f32 = 1000.0;
+
+// Cepstrum points computed per sample of quefrency
+const CEPSTRUM_OVERSAMPLING: usize = 4;
+
+/// Estimate the fundamental from the peak of the real cepstrum, the inverse FFT of the log
+/// magnitude spectrum.
+///
+/// Evenly spaced harmonics ripple the log spectrum once per fundamental, which shows up as a
+/// cepstral peak at the period in samples. Only periods of `min_freq` to `max_freq` Hz are
+/// searched, and never any outside 50–1,000 Hz, leaving out the low quefrencies that describe the
+/// spectral envelope. Returns `None` for silent input, a range that doesn't overlap 50–1,000 Hz,
+/// or input too short to hold the longest period searched.
+pub fn cepstrum_pitch(samples: &[f32], sample_rate: f32, min_freq: f32, max_freq: f32) -> Option<f32> {
+    cepstrum_pitch_with(samples, sample_rate, min_freq, max_freq, &FftCache::default())
+}
+
+// `cepstrum_pitch`, taking the FFT plans from `plans`
+pub(crate) fn cepstrum_pitch_with(
+    samples: &[f32],
+    sample_rate: f32,
+    min_freq: f32,
+    max_freq: f32,
+    plans: &FftCache,
+) -> Option<f32> {
+    let shortest_period = sample_rate / max_freq.min(CEPSTRUM_MAX_HZ);
+    let longest_period = sample_rate / min_freq.max(CEPSTRUM_MIN_HZ);
+    if (samples.len() as f32) < longest_period || samples.iter().all(|&s| s == 0.0) {
+        return None;
+    }
+
+    let size = samples.len().next_power_of_two();
+    let forward = plans.forward(size);
+    let inverse = plans.inverse(size * CEPSTRUM_OVERSAMPLING);
+
+    let mut buffer: Vec<f32> = samples.iter()
+        .enumerate()
+        .map(|(n, &s)| s * Window::Hann.value(n, samples.len()))
+        .collect();
+    buffer.resize(size, 0.0);
+    let mut spectrum = forward.make_output_vec();
+    forward.process(&mut buffer, &mut spectrum).expect("FFT buffer lengths don't match the plan");
+
+    // The log magnitude is real and even, so its inverse transform is the real cepstrum. Zero bins
+    // above Nyquist interpolate the cepstrum between samples: a period that falls between two
+    // samples would otherwise have its peak split in half and lose to the peak at twice the period.
+    let floor = spectrum.iter().map(|c| c.norm()).fold(0.0, f32::max) * 1e-3;
+    let mut log_spectrum = inverse.make_input_vec();
+    for (log, c) in log_spectrum.iter_mut().zip(&spectrum) {
+        *log = Complex::new(c.norm().max(floor).ln(), 0.0);
+    }
+    let mut cepstrum = inverse.make_output_vec();
+    inverse.process(&mut log_spectrum, &mut cepstrum).expect("FFT buffer lengths don't match the plan");
+
+    // Search in steps of 1 / CEPSTRUM_OVERSAMPLING samples
+    let steps = CEPSTRUM_OVERSAMPLING as f32;
+    let min_quefrency = ((shortest_period * steps).floor() as usize).max(1);
+    let max_quefrency = ((longest_period * steps).ceil() as usize).min(cepstrum.len() / 2 - 1);
+    if min_quefrency > max_quefrency {
+        return None;
+    }
+    let strongest = cepstrum[min_quefrency..=max_quefrency].iter().cloned().fold(f32::MIN, f32::max);
+    if strongest <= 0.0 {
+        return None;
+    }
+
+    // Harmonics make sharp ridges in the log spectrum, so the peaks at multiples of the period
+    // are nearly as strong as the period's own; take the first local maximum close to the
+    // strongest, as later ones would report a lower octave
+    let peak = (min_quefrency..=max_quefrency).find(|&q| {
+        cepstrum[q] >= 0.9 * strongest && cepstrum[q] >= cepstrum[q - 1] && cepstrum[q] >= cepstrum[q + 1]
+    })?;
+    let offset = parabolic_offset(cepstrum[peak - 1], cepstrum[peak], cepstrum[peak + 1]);
+    Some(sample_rate * steps / (peak as f32 + offset))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::PI;
+
+    // A band-limited glottal source at `f0`: harmonics falling off at 12 dB per octave, shaped by
+    // a single formant near 700 Hz
+    fn glottal_pulses(f0: f32, sample_rate: u32, len: usize) -> Vec<f32> {
+        let harmonics = (sample_rate as f32 / 2.0 / f0) as usize;
+        (0..len)
+            .map(|n| {
+                let t = n as f32 / sample_rate as f32;
+                (1..=harmonics)
+                    .map(|k| {
+                        let frequency = k as f32 * f0;
+                        let formant = 1.0 / (1.0 + ((frequency - 700.0) / 300.0).powi(2));
+                        (1.0 + 4.0 * formant) / (k * k) as f32 * (2.0 * PI * frequency * t).sin()
+                    })
+                    .sum()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn finds_the_fundamental_of_glottal_pulses() {
+        let sample_rate = 16_000;
+        for f0 in [110.0, 146.8, 220.0, 330.0] {
+            let samples = glottal_pulses(f0, sample_rate, 4096);
+            let pitch = cepstrum_pitch(&samples, sample_rate as f32, 50.0, 1000.0).unwrap();
+            assert!((pitch - f0).abs() / f0 < 0.01, "expected {f0} Hz, got {pitch} Hz");
+        }
+    }
+
+    #[test]
+    fn searches_only_between_min_and_max_freq() {
+        // A narrower range skips the fundamental, leaving the octave below it
+        let sample_rate = 16_000;
+        let samples = glottal_pulses(220.0, sample_rate, 4096);
+        let pitch = cepstrum_pitch(&samples, sample_rate as f32, 50.0, 150.0).unwrap();
+        assert!((pitch - 110.0).abs() < 1.1, "expected 110 Hz, got {pitch} Hz");
+        assert_eq!(cepstrum_pitch(&samples, sample_rate as f32, 1200.0, 4000.0), None);
+    }
+
+    #[test]
+    fn silence_has_no_pitch() {
+        assert_eq!(cepstrum_pitch(&[0.0; 4096], 16_000.0, 50.0, 1000.0), None);
+    }
+}
@@ -0,0 +1,36 @@
+use crate::frequency_to_midi;
+
+// Bins below the lowest piano note (A0) hold DC leakage and rumble rather than pitch
+const LOWEST_HZ: f32 = 27.5;
+
+/// Pitch-class profile of a magnitude spectrum: the magnitudes of all bins summed per pitch class.
+///
+/// Index 0 is C, 1 is C#, and so on up to B. Each bin counts toward the pitch class of its center
+/// frequency, rounded to the nearest note with A4 at `a4_hz`; bins below 27.5 Hz are ignored. The
+/// spectrum is the non-negative half of an `fft_size`-point FFT of audio at `sample_rate`.
+pub fn chromagram(magnitudes: &[f32], sample_rate: f32, fft_size: usize, a4_hz: f32) -> [f32; 12] {
+    let hz_per_bin = sample_rate / fft_size as f32;
+    let mut chroma = [0.0; 12];
+    for (bin, &magnitude) in magnitudes.iter().enumerate() {
+        let frequency = bin as f32 * hz_per_bin;
+        if frequency >= LOWEST_HZ {
+            chroma[frequency_to_midi(frequency, a4_hz).rem_euclid(12) as usize] += magnitude;
+        }
+    }
+    chroma
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn folds_bins_by_the_given_tuning() {
+        // 1 Hz per bin, with only the bin at 427 Hz sounding: a G# with A4 at 440 Hz, but an A
+        // with A4 at 432 Hz
+        let mut magnitudes = vec![0.0; 4001];
+        magnitudes[427] = 1.0;
+        assert_eq!(chromagram(&magnitudes, 8000.0, 8000, 440.0)[8], 1.0);
+        assert_eq!(chromagram(&magnitudes, 8000.0, 8000, 432.0)[9], 1.0);
+    }
+}
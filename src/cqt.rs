@@ -0,0 +1,102 @@
+use crate::{Interpolation, Window};
+use std::f32::consts::PI;
+
+/// Constant-Q bins per octave used by [`Method::Cqt`](crate::Method::Cqt), three per semitone.
+pub const CQT_BINS_PER_OCTAVE: usize = 36;
+
+/// Constant-Q transform: magnitudes of geometrically spaced bins from `f_min` up to Nyquist.
+///
+/// Bin `k` is centered on `f_min * 2^(k / bins_per_octave)` and every bin spans the same number
+/// of cycles, so each semitone gets the same resolution. Each bin's Hann-windowed kernel is
+/// slid across the whole input in non-overlapping steps and the magnitudes are averaged. The input
+/// is zero-padded to any kernel longer than it, so its low bins read the one padded block at the
+/// resolution of every other input rather than a coarser one.
+pub fn cqt(samples: &[f32], sample_rate: f32, bins_per_octave: usize, f_min: f32) -> Vec<f32> {
+    if samples.is_empty() || bins_per_octave == 0 || f_min <= 0.0 {
+        return Vec::new();
+    }
+    let bins_per_octave = bins_per_octave as f32;
+    let q = 1.0 / (2f32.powf(1.0 / bins_per_octave) - 1.0);
+    let nyquist = sample_rate / 2.0;
+
+    (0..)
+        .map(|k| f_min * 2f32.powf(k as f32 / bins_per_octave))
+        .take_while(|&frequency| frequency < nyquist)
+        .map(|frequency| {
+            let length = ((q * sample_rate / frequency).ceil() as usize).max(1);
+            let omega = 2.0 * PI * frequency / sample_rate;
+            let kernel: Vec<(f32, f32)> = (0..length)
+                .map(|n| {
+                    let w = Window::Hann.value(n, length) / length as f32;
+                    (w * (omega * n as f32).cos(), -w * (omega * n as f32).sin())
+                })
+                .collect();
+
+            // A block shorter than the kernel meets zeros past its end
+            let project = |block: &[f32]| {
+                let (re, im) = block.iter().zip(&kernel).fold((0.0, 0.0), |(re, im), (&x, &(kr, ki))| {
+                    (re + x * kr, im + x * ki)
+                });
+                (re * re + im * im).sqrt()
+            };
+            if samples.len() < length {
+                return project(samples);
+            }
+            let blocks: Vec<f32> = samples.chunks_exact(length).map(project).collect();
+            blocks.iter().sum::<f32>() / blocks.len() as f32
+        })
+        .collect()
+}
+
+// Frequency of the strongest constant-Q bin between `min_freq` and `max_freq`, refined between
+// bins on the log-frequency axis
+pub(crate) fn cqt_pitch(
+    samples: &[f32],
+    sample_rate: f32,
+    min_freq: f32,
+    max_freq: f32,
+    interpolation: Interpolation,
+) -> Option<f32> {
+    let f_min = min_freq.max(1.0);
+    let magnitudes = cqt(samples, sample_rate, CQT_BINS_PER_OCTAVE, f_min);
+    let bin_frequency = |bin: f32| f_min * 2f32.powf(bin / CQT_BINS_PER_OCTAVE as f32);
+
+    let peak = magnitudes.iter()
+        .enumerate()
+        .take_while(|&(bin, _)| bin_frequency(bin as f32) <= max_freq)
+        .max_by(|a, b| a.1.total_cmp(b.1))
+        .filter(|&(_, &magnitude)| magnitude > 0.0)?
+        .0;
+    Some(bin_frequency(peak as f32 + interpolation.offset(&magnitudes, peak)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::sine;
+    use crate::{analyze, Method, Notation, Settings, A4_HZ};
+
+    #[test]
+    fn resolves_a_low_e() {
+        // E2, the lowest string of a guitar, whose kernel spans a little over half the second
+        let tone = sine(82.41, 8000, 8000);
+        for interpolation in [Interpolation::Parabolic, Interpolation::Gaussian] {
+            let settings = Settings { method: Method::Cqt, downsample: 1, interpolation, ..Settings::default() };
+            let analysis = analyze(&tone, 8000, &settings).unwrap();
+            let frequency = analysis.frequency.unwrap();
+            assert!((frequency - 82.41).abs() < 1.0, "{:?}: {} Hz", interpolation, frequency);
+            assert_eq!(analysis.note(A4_HZ, Notation::default()).unwrap().note, "E2");
+        }
+    }
+
+    #[test]
+    fn zero_pads_input_shorter_than_a_kernel() {
+        // The 20 Hz kernel is about 20,600 samples long at 8 kHz, so a second of tone fills only
+        // part of it and the lowest bin reads lower than from a full kernel
+        let tone = sine(20.0, 40_000, 8000);
+        let full = cqt(&tone, 8000.0, CQT_BINS_PER_OCTAVE, 20.0);
+        let short = cqt(&tone[..8000], 8000.0, CQT_BINS_PER_OCTAVE, 20.0);
+        assert_eq!(short.len(), full.len());
+        assert!(short[0] < 0.5 * full[0], "{} against {}", short[0], full[0]);
+    }
+}
@@ -0,0 +1,88 @@
+use thiserror::Error;
+
+/// Everything that can go wrong loading or analyzing a recording.
+#[derive(Debug, Error)]
+pub enum Error {
+    /// The file couldn't be opened or read.
+    #[error("could not open file: {0}")]
+    FileOpen(#[source] std::io::Error),
+    /// The samples use a bit depth the loader doesn't handle.
+    #[error("unsupported bit depth: {bits}-bit {format} samples")]
+    UnsupportedBitDepth {
+        /// Bits per sample declared by the file.
+        bits: u16,
+        /// `"integer"` or `"float"`.
+        format: &'static str,
+    },
+    /// The file uses an encoding the loader doesn't handle.
+    #[error("unsupported audio format")]
+    UnsupportedFormat,
+    /// The file holds no samples.
+    #[error("the file contains no audio samples")]
+    EmptyFile,
+    /// The analyzed excerpt holds too few samples for a meaningful FFT.
+    #[error("input too short: {samples} samples to analyze, at least {required} needed")]
+    TooShort {
+        /// Samples in the excerpt after downsampling.
+        samples: usize,
+        /// Minimum the analysis needs.
+        required: usize,
+    },
+    /// The recording was to be resampled to a rate of 0 Hz.
+    #[error("the target sample rate must be above 0 Hz")]
+    ZeroTargetRate,
+    /// The FFT was to have fewer than 2 points.
+    #[error("the FFT size must be at least 2, not {size}")]
+    FftSizeTooSmall {
+        /// The FFT size requested.
+        size: usize,
+    },
+    /// A Gaussian window was to have a width that is not a positive, finite number.
+    #[error("the Gaussian window's sigma must be a finite number above 0, not {sigma}")]
+    InvalidWindowSigma {
+        /// The sigma requested.
+        sigma: f32,
+    },
+    /// Silence trimming found nothing but silence.
+    #[error("no signal above {threshold_dbfs} dBFS")]
+    NoSignal {
+        /// Level that counts as sound.
+        threshold_dbfs: f32,
+    },
+    /// No pitch was found in the searched frequency range.
+    #[error("no pitch detected")]
+    NoPitch,
+    /// The file is not a well-formed WAV file.
+    #[error("invalid WAV file: {0}")]
+    Wav(#[source] hound::Error),
+    /// The file is not a well-formed FLAC file.
+    #[error("invalid FLAC file: {0}")]
+    Flac(#[source] claxon::Error),
+    /// The file is not a well-formed Ogg Vorbis file.
+    #[error("invalid Ogg Vorbis file: {0}")]
+    Ogg(#[source] lewton::VorbisError),
+}
+
+impl From<hound::Error> for Error {
+    fn from(err: hound::Error) -> Self {
+        match err {
+            hound::Error::Unsupported => Error::UnsupportedFormat,
+            err => Error::Wav(err),
+        }
+    }
+}
+
+impl From<claxon::Error> for Error {
+    fn from(err: claxon::Error) -> Self {
+        match err {
+            claxon::Error::Unsupported(_) => Error::UnsupportedFormat,
+            err => Error::Flac(err),
+        }
+    }
+}
+
+impl From<lewton::VorbisError> for Error {
+    fn from(err: lewton::VorbisError) -> Self {
+        Error::Ogg(err)
+    }
+}
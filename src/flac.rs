@@ -0,0 +1,66 @@
+use crate::{int_scale, Audio, Error};
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::Path;
+
+/// Read a FLAC file without downmixing it.
+///
+/// Samples are normalized exactly like integer WAV samples of the same bit depth, so a FLAC and
+/// a WAV export of the same recording analyze identically.
+pub fn read_flac(path: &Path) -> Result<Audio, Error> {
+    let file = File::open(path).map_err(Error::FileOpen)?;
+    read_flac_from(BufReader::new(file))
+}
+
+/// Read a FLAC stream without downmixing it.
+pub fn read_flac_from<R: Read>(reader: R) -> Result<Audio, Error> {
+    let mut reader = claxon::FlacReader::new(reader)?;
+    let info = reader.streaminfo();
+
+    let bits = info.bits_per_sample as u16;
+    let scale = int_scale(bits).ok_or(Error::UnsupportedBitDepth { bits, format: "integer" })?;
+    let samples = reader.samples()
+        .map(|s| s.map(|s| s as f32 / scale))
+        .collect::<Result<Vec<f32>, _>>()?;
+    if samples.is_empty() {
+        return Err(Error::EmptyFile);
+    }
+
+    Ok(Audio {
+        samples,
+        channels: info.channels as usize,
+        sample_rate: info.sample_rate,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{mix, wav};
+    use std::io::Cursor;
+
+    // Half a second of A3 (220 Hz) at half scale, 22.05 kHz mono, as 16-bit samples and as the
+    // FLAC file tests/data/a3.flac encoded from them
+    const A3_FLAC: &[u8] = include_bytes!("../tests/data/a3.flac");
+
+    #[test]
+    fn detects_the_same_note_as_the_wav() {
+        let flac = read_flac_from(Cursor::new(A3_FLAC)).unwrap();
+        let a3 = mix(&[(220.0, 0.5)], 11_025, 22_050);
+        let wav = crate::read_wav_from(Cursor::new(wav(&a3, 1, 22_050, 16))).unwrap();
+        // FLAC is lossless, so the samples themselves match
+        assert_eq!(flac, wav);
+        let settings = crate::Settings::default();
+        let from_flac = crate::analyze(&flac.mono(), flac.sample_rate, &settings).unwrap();
+        let from_wav = crate::analyze(&wav.mono(), wav.sample_rate, &settings).unwrap();
+        assert_eq!(from_flac.max_index, from_wav.max_index);
+        let note = from_flac.note(crate::A4_HZ, crate::Notation::default()).unwrap();
+        assert_eq!(note.note, "A3");
+    }
+
+    #[test]
+    fn rejects_a_stream_that_isnt_flac() {
+        let result = read_flac_from(Cursor::new(b"RIFF\0\0\0\0WAVEfmt ".to_vec()));
+        assert!(matches!(result, Err(Error::Flac(_))), "{:?}", result);
+    }
+}
@@ -0,0 +1,96 @@
+use crate::midi_to_frequency;
+use serde::Serialize;
+use std::str::FromStr;
+
+// Open strings in standard tuning, lowest first, as (name, MIDI number)
+const GUITAR_STRINGS: [(&str, i32); 6] =
+    [("low E", 40), ("A", 45), ("D", 50), ("G", 55), ("B", 59), ("high E", 64)];
+const BASS_STRINGS: [(&str, i32); 4] = [("E", 28), ("A", 33), ("D", 38), ("G", 43)];
+
+/// An instrument whose open strings a tuner can match against, in standard tuning.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Instrument {
+    /// Six-string guitar: E2 A2 D3 G3 B3 E4.
+    Guitar,
+    /// Four-string bass: E1 A1 D2 G2.
+    Bass,
+}
+
+/// The open string closest to a frequency, as found by [`Instrument::nearest_string`].
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct StringMatch {
+    /// Name of the string, e.g. `A` or `low E`.
+    pub name: &'static str,
+    /// MIDI number of the open string's note.
+    pub midi: i32,
+    /// Signed deviation from the open string in cents; positive means tune down.
+    pub cents: f32,
+}
+
+impl Instrument {
+    /// The open strings, lowest first, as (name, MIDI number).
+    pub fn strings(self) -> &'static [(&'static str, i32)] {
+        match self {
+            Instrument::Guitar => &GUITAR_STRINGS,
+            Instrument::Bass => &BASS_STRINGS,
+        }
+    }
+
+    /// The open string whose pitch is closest to `frequency` in cents, with A4 tuned to `a4_hz`.
+    pub fn nearest_string(self, frequency: f32, a4_hz: f32) -> StringMatch {
+        self.strings()
+            .iter()
+            .map(|&(name, midi)| StringMatch {
+                name,
+                midi,
+                cents: 1200.0 * (frequency / midi_to_frequency(midi, a4_hz)).log2(),
+            })
+            .min_by(|a, b| a.cents.abs().total_cmp(&b.cents.abs()))
+            .expect("every instrument has strings")
+    }
+}
+
+impl StringMatch {
+    /// Which way to turn the peg: "tune up" when flat, "tune down" when sharp, or "in tune"
+    /// within half a cent.
+    pub fn direction(&self) -> &'static str {
+        match self.cents {
+            cents if cents.abs() < 0.5 => "in tune",
+            cents if cents < 0.0 => "tune up",
+            _ => "tune down",
+        }
+    }
+}
+
+impl FromStr for Instrument {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "guitar" => Ok(Instrument::Guitar),
+            "bass" => Ok(Instrument::Bass),
+            _ => Err("expected guitar or bass".to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_the_nearest_open_string() {
+        // 8 cents sharp of A2 at 110 Hz
+        let a = Instrument::Guitar.nearest_string(110.0 * 2f32.powf(8.0 / 1200.0), 440.0);
+        assert_eq!((a.name, a.midi), ("A", 45));
+        assert!((a.cents - 8.0).abs() < 0.01, "cents {}", a.cents);
+        assert_eq!(a.direction(), "tune down");
+
+        let high_e = Instrument::Guitar.nearest_string(320.0, 440.0);
+        assert_eq!(high_e.name, "high E");
+        assert_eq!(high_e.direction(), "tune up");
+
+        let low_e = Instrument::Bass.nearest_string(41.2, 440.0);
+        assert_eq!((low_e.name, low_e.midi, low_e.direction()), ("E", 28, "in tune"));
+    }
+}
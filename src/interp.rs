@@ -0,0 +1,73 @@
+use crate::parabolic_offset;
+use std::str::FromStr;
+
+/// How the position of a spectral peak is refined between bins.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Interpolation {
+    /// [`interp_parabolic`]: a parabola through the linear magnitudes, biased toward the center
+    /// bin for windowed tones.
+    Parabolic,
+    /// [`interp_gaussian`]: a parabola through the log magnitudes, exact for a Gaussian peak and
+    /// close for the main lobe of a Hann-windowed tone.
+    #[default]
+    Gaussian,
+}
+
+impl Interpolation {
+    /// Fractional offset, within ±0.5 bins, of the true peak near bin `index` of `magnitudes`.
+    ///
+    /// A peak in the first or last bin has no neighbour on one side and gets an offset of 0.0.
+    pub fn offset(self, magnitudes: &[f32], index: usize) -> f32 {
+        if index == 0 || index + 1 >= magnitudes.len() {
+            return 0.0;
+        }
+        let (left, center, right) = (magnitudes[index - 1], magnitudes[index], magnitudes[index + 1]);
+        match self {
+            Interpolation::Parabolic => interp_parabolic(left, center, right),
+            Interpolation::Gaussian => interp_gaussian(left, center, right),
+        }
+    }
+}
+
+impl FromStr for Interpolation {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "parabolic" => Ok(Interpolation::Parabolic),
+            "gaussian" => Ok(Interpolation::Gaussian),
+            _ => Err("expected parabolic or gaussian".to_string()),
+        }
+    }
+}
+
+/// Fractional bin offset of the vertex of the parabola through three neighbouring magnitudes.
+pub fn interp_parabolic(left: f32, center: f32, right: f32) -> f32 {
+    parabolic_offset(left, center, right)
+}
+
+/// Fractional bin offset of the Gaussian through three neighbouring magnitudes, i.e. the parabola
+/// through their logarithms.
+pub fn interp_gaussian(left: f32, center: f32, right: f32) -> f32 {
+    // Clamp to a tiny positive value so silent bins don't produce -inf
+    let ln = |magnitude: f32| magnitude.max(f32::MIN_POSITIVE).ln();
+    parabolic_offset(ln(left), ln(center), ln(right))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::sine;
+    use crate::{analyze, Settings, Window};
+
+    #[test]
+    fn gaussian_is_closer_than_parabolic_for_a_hann_windowed_tone() {
+        // 4096 samples at 8 kHz need no zero-padding, and 440 Hz falls between bins 225 and 226
+        let samples = sine(440.0, 4096, 8000);
+        let error = |interpolation| {
+            let settings = Settings { downsample: 1, window: Window::Hann, interpolation, ..Settings::default() };
+            (analyze(&samples, 8000, &settings).unwrap().frequency.unwrap() - 440.0).abs()
+        };
+        assert!(error(Interpolation::Gaussian) < error(Interpolation::Parabolic));
+    }
+}
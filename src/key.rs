@@ -0,0 +1,110 @@
+use crate::Notation;
+use std::fmt;
+
+// Krumhansl-Kessler probe-tone ratings of each scale degree, tonic first
+const MAJOR_PROFILE: [f32; 12] = [6.35, 2.23, 3.48, 2.33, 4.38, 4.09, 2.52, 5.19, 2.39, 3.66, 2.29, 2.88];
+const MINOR_PROFILE: [f32; 12] = [6.33, 2.68, 3.52, 5.38, 2.60, 3.53, 2.54, 4.75, 3.98, 2.69, 3.34, 3.17];
+
+/// Major or minor mode of a [`Key`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    Major,
+    Minor,
+}
+
+/// A musical key, e.g. G major.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Key {
+    /// Pitch class of the tonic, 0 for C up to 11 for B.
+    pub tonic: usize,
+    /// Major or minor.
+    pub mode: Mode,
+    /// Pearson correlation of the chromagram with the key's profile, from -1.0 to 1.0.
+    pub correlation: f32,
+}
+
+impl Key {
+    /// Name of the key with its tonic in `notation`, e.g. `G major`.
+    pub fn name(&self, notation: Notation) -> String {
+        let mode = match self.mode {
+            Mode::Major => "major",
+            Mode::Minor => "minor",
+        };
+        format!("{} {}", notation.pitch_class(self.tonic as i32), mode)
+    }
+}
+
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.name(Notation::default()))
+    }
+}
+
+/// Estimate the key of a [`chromagram`](crate::chromagram) with the Krumhansl-Schmuckler
+/// algorithm: the best correlating of the 24 major and minor key profiles wins.
+pub fn estimate_key(chroma: &[f32; 12]) -> Key {
+    let mut best = Key { tonic: 0, mode: Mode::Major, correlation: f32::NEG_INFINITY };
+    for (mode, profile) in [(Mode::Major, &MAJOR_PROFILE), (Mode::Minor, &MINOR_PROFILE)] {
+        for tonic in 0..12 {
+            // Rotate the chromagram so the candidate tonic lines up with the profile's first degree
+            let rotated: Vec<f32> = (0..12).map(|degree| chroma[(tonic + degree) % 12]).collect();
+            let correlation = pearson(&rotated, profile);
+            if correlation > best.correlation {
+                best = Key { tonic, mode, correlation };
+            }
+        }
+    }
+    best
+}
+
+// Pearson correlation coefficient of two equally long series, 0.0 if either is constant
+fn pearson(a: &[f32], b: &[f32]) -> f32 {
+    let mean = |x: &[f32]| x.iter().sum::<f32>() / x.len() as f32;
+    let (mean_a, mean_b) = (mean(a), mean(b));
+    let (mut covariance, mut var_a, mut var_b) = (0.0, 0.0, 0.0);
+    for (x, y) in a.iter().zip(b) {
+        covariance += (x - mean_a) * (y - mean_b);
+        var_a += (x - mean_a) * (x - mean_a);
+        var_b += (y - mean_b) * (y - mean_b);
+    }
+    if var_a == 0.0 || var_b == 0.0 {
+        return 0.0;
+    }
+    covariance / (var_a * var_b).sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Chromagram counting each pitch class once for every chord it's in
+    fn chords(chords: &[[usize; 3]]) -> [f32; 12] {
+        let mut chroma = [0.0; 12];
+        for &class in chords.iter().flatten() {
+            chroma[class] += 1.0;
+        }
+        chroma
+    }
+
+    #[test]
+    fn finds_c_major_in_its_primary_chords() {
+        // C, F and G major: I, IV and V
+        let key = estimate_key(&chords(&[[0, 4, 7], [5, 9, 0], [7, 11, 2]]));
+        assert_eq!((key.tonic, key.mode), (0, Mode::Major));
+        assert_eq!(key.name(Notation::default()), "C major");
+    }
+
+    #[test]
+    fn finds_a_minor() {
+        // A minor, D minor, E major and back to A minor: i, iv, V, i
+        let key = estimate_key(&chords(&[[9, 0, 4], [2, 5, 9], [4, 8, 11], [9, 0, 4]]));
+        assert_eq!((key.tonic, key.mode), (9, Mode::Minor));
+        assert!(key.correlation > 0.9, "{}", key.correlation);
+    }
+
+    #[test]
+    fn a_flat_chromagram_correlates_with_no_key() {
+        let key = estimate_key(&[1.0; 12]);
+        assert_eq!(key.correlation, 0.0);
+    }
+}
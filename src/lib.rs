@@ -0,0 +1,1785 @@
+// Analysis only computes and returns its results; printing them is left to the caller
+#![deny(clippy::print_stdout, clippy::print_stderr, clippy::dbg_macro)]
+
+use serde::Serialize;
+use cepstrum::cepstrum_pitch_with;
+use cqt::cqt_pitch;
+use plan::FftCache;
+use realfft::RealToComplex;
+use std::f32::consts::{LN_10, PI};
+use std::path::Path;
+use std::fs::File;
+use std::io::{BufReader, Cursor, Read};
+use std::ops::RangeInclusive;
+use std::str::FromStr;
+
+mod abc;
+mod analyzer;
+mod cepstrum;
+mod chroma;
+mod cqt;
+mod error;
+mod flac;
+mod instrument;
+mod interp;
+mod key;
+mod lilypond;
+mod metadata;
+mod midi;
+mod musicxml;
+mod notation;
+mod ogg;
+mod onset;
+mod phase;
+mod peaks;
+mod plan;
+mod quantize;
+mod resample;
+mod segment;
+mod smooth;
+mod stft;
+mod streaming;
+mod synth;
+mod tempo;
+#[cfg(test)]
+mod test_util;
+mod tuner;
+mod vibrato;
+mod window;
+
+pub use abc::{to_abc, DEFAULT_BPM};
+pub use analyzer::{Transcription, WavAnalyzer};
+pub use cepstrum::cepstrum_pitch;
+pub use chroma::chromagram;
+pub use cqt::{cqt, CQT_BINS_PER_OCTAVE};
+pub use error::Error;
+pub use flac::{read_flac, read_flac_from};
+pub use instrument::{Instrument, StringMatch};
+pub use interp::{interp_gaussian, interp_parabolic, Interpolation};
+pub use key::{estimate_key, Key, Mode};
+pub use lilypond::to_lilypond;
+pub use metadata::{read_wav_metadata, read_wav_metadata_from, SampleLoop, WavMetadata};
+pub use midi::{write_midi, write_midi_to, VelocityCurve};
+pub use musicxml::to_musicxml;
+pub use notation::Notation;
+pub use ogg::{read_ogg, read_ogg_from};
+pub use onset::{detect_onsets, spectral_flux};
+pub use phase::instantaneous_frequency;
+pub use peaks::{
+    find_peaks, find_peaks_above, fundamentals, noise_floor, HARMONIC_TOLERANCE_CENTS, NOISE_FLOOR_FACTOR,
+    POLY_MAX_PEAKS, POLY_THRESHOLD,
+};
+pub use quantize::{quantize, QuantizedNote, DEFAULT_GRID};
+pub use resample::resample;
+pub use segment::{drop_short_notes, segment_notes};
+pub use smooth::{median_filter, moving_average};
+pub use stft::{frame_pitches, frame_rms, pitch_track, stft, PitchEstimate, DEFAULT_FRAME, DEFAULT_HOP};
+pub use streaming::{detect_notes_streaming, NoteStream, StreamConfig};
+pub use synth::{render_notes, sine, write_wav};
+pub use tempo::{estimate_bpm, TEMPO_FRAME, TEMPO_HOP};
+pub use tuner::{goertzel, tuner_pitch};
+pub use vibrato::{note_vibrato, vibrato, Vibrato, VIBRATO_FRAME, VIBRATO_HOP};
+pub use window::{apply_window, Window, DEFAULT_GAUSSIAN_SIGMA};
+
+/// The note detected in a recording.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct NoteResult {
+    /// Dominant frequency in Hz.
+    pub frequency: f32,
+    /// Closest note name, e.g. `A4`.
+    pub note: String,
+    /// MIDI number of the closest note (A4 = 69).
+    pub midi: i32,
+    /// Signed deviation from the closest note in cents.
+    pub cents: f32,
+    /// How much of the searched spectrum's energy lies at the detected frequency, from 0.0 to 1.0
+    /// (see [`peak_confidence`]). Close to 1.0 for a clean tone, low for noise. For the methods
+    /// other than FFT this measures the spectrum at their estimate, so it's low too when the
+    /// spectrum doesn't bear that estimate out.
+    pub confidence: f32,
+    /// RMS level of the analyzed excerpt, from 0.0 to 1.0.
+    pub rms: f32,
+    /// The RMS level in dBFS (see [`to_dbfs`]).
+    pub dbfs: f32,
+}
+
+impl NoteResult {
+    /// The same reading with its note `semitones` higher, or lower for negative values, named in
+    /// `notation`. The frequency, cents and levels stay those measured.
+    pub fn transposed(&self, semitones: i32, notation: Notation) -> NoteResult {
+        NoteResult { note: notation.note_name(self.midi + semitones), midi: self.midi + semitones, ..self.clone() }
+    }
+}
+
+/// One of the harmonics of the detected note, as listed by [`Analysis::harmonics`].
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Harmonic {
+    /// Which multiple of the fundamental this is, 1 for the fundamental itself.
+    pub number: usize,
+    /// Interpolated frequency of the harmonic's peak in Hz.
+    pub frequency: f32,
+    /// Level of the peak relative to the fundamental's in dB, 0.0 for the fundamental.
+    pub level_db: f32,
+}
+
+/// Default number of harmonics, the fundamental included, listed by [`Analysis::harmonics`].
+pub const HARMONIC_COUNT: usize = 8;
+
+/// One of the strongest peaks in a spectrum, as listed by [`Analysis::top_peaks`].
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SpectralPeak {
+    /// Interpolated frequency of the peak in Hz.
+    pub frequency: f32,
+    /// Magnitude of the peak's bin.
+    pub magnitude: f32,
+    /// Closest note name, e.g. `A4`.
+    pub note: String,
+    /// MIDI number of the closest note (A4 = 69).
+    pub midi: i32,
+}
+
+/// A note sounding over a span of time.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct NoteEvent {
+    /// MIDI number of the note (A4 = 69).
+    pub midi: i32,
+    /// Onset time in seconds.
+    pub start_sec: f32,
+    /// Release time in seconds.
+    pub end_sec: f32,
+    /// RMS level over the note's frames, from 0.0 to 1.0.
+    pub rms: f32,
+}
+
+impl NoteEvent {
+    /// Length of the note in seconds.
+    pub fn duration(&self) -> f32 {
+        self.end_sec - self.start_sec
+    }
+
+    /// The same note `semitones` higher, or lower for negative values, at the same times.
+    pub fn transposed(&self, semitones: i32) -> NoteEvent {
+        NoteEvent { midi: self.midi + semitones, ..self.clone() }
+    }
+}
+
+/// Standard concert pitch for A4 in Hz.
+pub const A4_HZ: f32 = 440.0;
+
+/// Pitch-detection algorithm used to estimate the fundamental.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Method {
+    /// Pick the strongest peak of the magnitude spectrum.
+    #[default]
+    Fft,
+    /// Pick the first strong peak of the time-domain autocorrelation.
+    Autocorr,
+    /// The YIN estimator with [`YIN_THRESHOLD`].
+    Yin,
+    /// Harmonic Product Spectrum over [`HPS_HARMONICS`] harmonics.
+    Hps,
+    /// Pick the strongest bin of a [`cqt`] with [`CQT_BINS_PER_OCTAVE`] bins per octave.
+    Cqt,
+    /// Pick the peak of the real cepstrum with [`cepstrum_pitch`].
+    Cepstrum,
+}
+
+/// Number of harmonics multiplied together by the Harmonic Product Spectrum.
+pub const HPS_HARMONICS: usize = 5;
+
+/// Default absolute threshold on YIN's cumulative mean normalized difference.
+pub const YIN_THRESHOLD: f32 = 0.1;
+
+/// Fraction of the peak's magnitude a subharmonic must reach for [`fix_octave`] to move down to it.
+pub const SUBHARMONIC_THRESHOLD: f32 = 0.2;
+
+impl FromStr for Method {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "fft" => Ok(Method::Fft),
+            "autocorr" => Ok(Method::Autocorr),
+            "yin" => Ok(Method::Yin),
+            "hps" => Ok(Method::Hps),
+            "cqt" => Ok(Method::Cqt),
+            "cepstrum" => Ok(Method::Cepstrum),
+            _ => Err("expected fft, autocorr, yin, hps, cqt or cepstrum".to_string()),
+        }
+    }
+}
+
+/// Default decimation factor, which keeps content up to about 2.7 kHz at 44.1 kHz.
+pub const DEFAULT_DOWNSAMPLE: usize = 8;
+
+/// Frequency in Hz above which [`Settings::multires`] reads the spectrum from short frames.
+pub const MULTIRES_CROSSOVER_HZ: f32 = 1000.0;
+
+/// How many times shorter than the excerpt's frames [`Settings::multires`]'s short frames are.
+pub const MULTIRES_RATIO: usize = 32;
+
+/// Fewest samples, after downsampling, that [`analyze`] will run a meaningful FFT on.
+pub const MIN_ANALYSIS_SAMPLES: usize = 64;
+
+/// Knobs for [`analyze`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Settings {
+    /// Pitch-detection algorithm.
+    pub method: Method,
+    /// Integer decimation factor applied before analysis, unless `target_rate` is set.
+    pub downsample: usize,
+    /// Rate in Hz to [`resample`] the recording to before analysis, in place of decimating it.
+    pub target_rate: Option<u32>,
+    /// Window applied before the FFT.
+    pub window: Window,
+    /// FFT length, at least 2; frames are zero-padded or cut to it. `None` pads each frame to
+    /// the next power of two.
+    pub fft_size: Option<usize>,
+    /// Offset of the analyzed excerpt from the start of the recording, in seconds.
+    pub start_sec: f32,
+    /// Length of the analyzed excerpt in seconds.
+    pub duration_sec: f32,
+    /// Lowest frequency the peak search considers, in Hz.
+    pub min_freq: f32,
+    /// Highest frequency the peak search considers and a note may be reported for, in Hz.
+    pub max_freq: f32,
+    /// Subtract the excerpt's mean before analysis (see [`remove_dc`]).
+    pub remove_dc: bool,
+    /// How the FFT or constant-Q peak is refined between bins.
+    pub interpolation: Interpolation,
+    /// First-order emphasis filter applied to the excerpt before analysis, if any.
+    pub emphasis: Option<Emphasis>,
+    /// Scale the excerpt to a peak of 1.0 before analysis (see [`normalize`]).
+    pub normalize: bool,
+    /// Start the excerpt at the first sound rather than the start of the recording (see
+    /// [`first_sound`]).
+    pub trim_silence: bool,
+    /// Level in dBFS that counts as sound when trimming silence.
+    pub silence_dbfs: f32,
+    /// Move the FFT peak down an octave while half its frequency also carries energy (see
+    /// [`fix_octave`]).
+    pub fix_octave: bool,
+    /// Number of half-overlapping frames the excerpt is split into, whose magnitude spectra are
+    /// averaged before the peak search; 1 transforms the whole excerpt at once.
+    pub average_frames: usize,
+    /// Read the spectrum above [`MULTIRES_CROSSOVER_HZ`] from frames [`MULTIRES_RATIO`] times
+    /// shorter, which follow overtones that waver in pitch better than one long frame.
+    pub multires: bool,
+    /// Width in bins of the [`moving_average`] the peak search runs on, so noise can't move the
+    /// peak from bin to bin; the peak is still refined on the raw magnitudes. 1 doesn't smooth.
+    pub smooth_spectrum: usize,
+    /// Frequencies in Hz outside which the magnitudes are set to zero, whatever the search range,
+    /// so no peak outside it is found for the note, the chord or anything else. `None` keeps the
+    /// whole spectrum.
+    pub band: Option<RangeInclusive<f32>>,
+    /// Refine the detected frequency with its [`instantaneous_frequency`], for reading a steady
+    /// test tone to a small fraction of a hertz.
+    pub precise: bool,
+}
+
+/// Default level in dBFS below which leading audio counts as silence.
+pub const DEFAULT_SILENCE_DBFS: f32 = -50.0;
+
+/// Default fraction of clipped samples above which a recording is reported as clipped (see
+/// [`clipping_ratio`]).
+pub const DEFAULT_CLIP_THRESHOLD: f32 = 0.001;
+
+// Samples at least this close to full scale count as clipped when enough of them run together;
+// a single sample there is just a loud peak
+const CLIP_LEVEL: f32 = 0.999;
+const MIN_CLIP_RUN: usize = 3;
+
+/// First-order filter that tilts the spectrum before analysis.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Emphasis {
+    /// Boost high frequencies with [`pre_emphasis`] using this coefficient.
+    Pre(f32),
+    /// Boost low frequencies with [`de_emphasis`] using this coefficient.
+    De(f32),
+}
+
+impl Default for Settings {
+    /// FFT peak picking between 20 Hz and 4,000 Hz with a Hann window over the first 2 seconds in a
+    /// single frame, downsampled by 8, with DC removal but without emphasis, normalization, silence
+    /// trimming, octave correction, multi-resolution analysis, spectral smoothing, band mask or
+    /// phase refinement.
+    fn default() -> Self {
+        Settings {
+            method: Method::default(),
+            downsample: DEFAULT_DOWNSAMPLE,
+            target_rate: None,
+            window: Window::default(),
+            fft_size: None,
+            start_sec: 0.0,
+            duration_sec: 2.0,
+            min_freq: 20.0,
+            max_freq: 4000.0,
+            remove_dc: true,
+            interpolation: Interpolation::default(),
+            emphasis: None,
+            normalize: false,
+            trim_silence: false,
+            silence_dbfs: DEFAULT_SILENCE_DBFS,
+            fix_octave: false,
+            average_frames: 1,
+            multires: false,
+            smooth_spectrum: 1,
+            band: None,
+            precise: false,
+        }
+    }
+}
+
+impl Settings {
+    /// Mono samples at the rate they're analyzed at, returned with that rate: resampled up or down
+    /// to `target_rate` if it's set, or otherwise decimated by `downsample`.
+    pub fn convert_rate(&self, samples: &[f32], sample_rate: u32) -> (Vec<f32>, f32) {
+        match self.target_rate {
+            Some(rate) => (resample(samples, sample_rate, rate), rate as f32),
+            None => downsample(samples, sample_rate, self.downsample),
+        }
+    }
+
+    /// The samples between `start_sec` and `start_sec + duration_sec`, clamped to those available.
+    ///
+    /// With `trim_silence`, both times count from the first sound instead, and a recording that
+    /// never rises above `silence_dbfs` fails with [`Error::NoSignal`].
+    pub fn excerpt<'a>(&self, samples: &'a [f32], sample_rate: f32) -> Result<&'a [f32], Error> {
+        let samples = if self.trim_silence {
+            let onset = first_sound(samples, sample_rate, self.silence_dbfs)
+                .ok_or(Error::NoSignal { threshold_dbfs: self.silence_dbfs })?;
+            &samples[onset..]
+        } else {
+            samples
+        };
+        let to_index = |seconds: f32| ((seconds.max(0.0) * sample_rate) as usize).min(samples.len());
+        let start = to_index(self.start_sec);
+        let end = to_index(self.start_sec + self.duration_sec).max(start);
+        Ok(&samples[start..end])
+    }
+}
+
+/// Everything the pipeline computed on its way to a note, for callers that want to inspect it.
+#[derive(Debug, Clone)]
+pub struct Analysis {
+    /// Sample rate of the analyzed signal after downsampling, exact rather than rounded to whole
+    /// hertz (44.1 kHz decimated by 8 is 5512.5 Hz).
+    pub sample_rate: f32,
+    /// Number of samples analyzed, before zero-padding.
+    pub window_size: usize,
+    /// FFT length: the window size zero-padded to the next power of two, or the `fft_size` set.
+    pub fft_size: usize,
+    /// Magnitudes of the non-negative half of the spectrum (`fft_size / 2 + 1` bins), divided by
+    /// the window's coherent gain so they don't depend on the window chosen.
+    pub magnitudes: Vec<f32>,
+    /// Bin holding the largest magnitude in the searched range, `None` if the range holds no bins.
+    pub max_index: Option<usize>,
+    /// Estimated fundamental frequency in Hz, if the method found one.
+    pub frequency: Option<f32>,
+    /// Lowest frequency searched, in Hz.
+    pub min_freq: f32,
+    /// Highest frequency searched, in Hz.
+    pub max_freq: f32,
+    /// RMS level of the analyzed excerpt, after DC removal but before normalization.
+    pub rms: f32,
+    /// How peaks were refined between bins.
+    pub interpolation: Interpolation,
+    /// Method that estimated [`frequency`](Self::frequency).
+    pub method: Method,
+}
+
+impl Analysis {
+    /// Length of the analyzed excerpt in seconds.
+    pub fn duration(&self) -> f32 {
+        self.window_size as f32 / self.sample_rate
+    }
+
+    /// Center frequency of a (possibly fractional) bin in Hz.
+    pub fn bin_frequency(&self, bin: f32) -> f32 {
+        bin * self.sample_rate / self.fft_size as f32
+    }
+
+    /// Bins whose frequency lies in the searched range.
+    pub fn search_bins(&self) -> RangeInclusive<usize> {
+        let hz_per_bin = self.bin_frequency(1.0);
+        bin_range(self.min_freq, self.max_freq, hz_per_bin, self.magnitudes.len())
+    }
+
+    /// Number of distinct notes sounding together: the [`fundamentals`] among the same peaks as
+    /// [`chord`](Self::chord) picks, so a note's overtones don't count as further notes.
+    pub fn polyphony(&self, threshold_ratio: f32, floor_factor: f32, max_peaks: usize) -> usize {
+        fundamentals(&self.chord_peaks(threshold_ratio, floor_factor, max_peaks)).len()
+    }
+
+    // Interpolated frequencies of the peaks `chord` is built from, strongest first
+    fn chord_peaks(&self, threshold_ratio: f32, floor_factor: f32, max_peaks: usize) -> Vec<f32> {
+        let search_bins = self.search_bins();
+        let Some(searched) = self.magnitudes.get(search_bins.clone()) else {
+            return Vec::new();
+        };
+
+        let largest = searched.iter().cloned().fold(0.0, f32::max);
+        let threshold = (threshold_ratio * largest).max(noise_floor(searched) * floor_factor);
+        find_peaks_above(searched, threshold, max_peaks)
+            .into_iter()
+            .map(|peak| {
+                let bin = search_bins.start() + peak;
+                self.bin_frequency(bin as f32 + self.interpolation.offset(&self.magnitudes, bin))
+            })
+            .collect()
+    }
+
+    /// Pitch classes sounding together, lowest first, e.g. `["C", "E", "G"]` for a C major triad.
+    ///
+    /// Uses the [`find_peaks_above`] of the searched range that reach `threshold_ratio` of its
+    /// largest magnitude and exceed its [`noise_floor`] by `floor_factor`, at most `max_peaks` of
+    /// them, with A4 tuned to `a4_hz` and names in `notation`.
+    pub fn chord(
+        &self,
+        a4_hz: f32,
+        notation: Notation,
+        threshold_ratio: f32,
+        floor_factor: f32,
+        max_peaks: usize,
+    ) -> Vec<&'static str> {
+        let mut midis: Vec<i32> = self.chord_peaks(threshold_ratio, floor_factor, max_peaks)
+            .into_iter()
+            .map(|frequency| frequency_to_midi(frequency, a4_hz))
+            .collect();
+        midis.sort();
+
+        let mut names: Vec<&'static str> = Vec::new();
+        for midi in midis {
+            let name = notation.pitch_class(midi);
+            if !names.contains(&name) {
+                names.push(name);
+            }
+        }
+        names
+    }
+
+    /// The `count` strongest local maxima of the searched range, strongest first, with notes
+    /// named in `notation` relative to A4 at `a4_hz`.
+    pub fn top_peaks(&self, count: usize, a4_hz: f32, notation: Notation) -> Vec<SpectralPeak> {
+        let search_bins = self.search_bins();
+        let Some(searched) = self.magnitudes.get(search_bins.clone()) else {
+            return Vec::new();
+        };
+
+        find_peaks(searched, 0.0, count)
+            .into_iter()
+            .map(|peak| {
+                let bin = search_bins.start() + peak;
+                let frequency = self.bin_frequency(bin as f32 + self.interpolation.offset(&self.magnitudes, bin));
+                let midi = frequency_to_midi(frequency, a4_hz);
+                SpectralPeak { frequency, magnitude: self.magnitudes[bin], note: notation.note_name(midi), midi }
+            })
+            .collect()
+    }
+
+    /// The fundamental and its overtones up to the `count`th harmonic, or as many as lie below the
+    /// Nyquist frequency; empty without a detected frequency.
+    ///
+    /// Harmonic `k` is the strongest bin within a quarter tone of `k` times the fundamental, so
+    /// slightly detuned overtones, like a piano string's, are still found, refined between bins
+    /// like the fundamental. Levels compare the heights of the peaks interpolated between bins.
+    pub fn harmonics(&self, count: usize) -> Vec<Harmonic> {
+        let Some(fundamental) = self.frequency.filter(|&frequency| frequency > 0.0) else {
+            return Vec::new();
+        };
+        let Some(last_bin) = self.magnitudes.len().checked_sub(1) else {
+            return Vec::new();
+        };
+        let hz_per_bin = self.bin_frequency(1.0);
+        let peak = |number: usize| {
+            let expected = number as f32 * fundamental / hz_per_bin;
+            // A quarter tone either side, but at least the neighbouring bins
+            let reach = (expected * (2f32.powf(50.0 / 1200.0) - 1.0)).max(1.0);
+            let low = (expected - reach).round().max(0.0) as usize;
+            let high = ((expected + reach).round() as usize).min(last_bin);
+            let bin = (low..=high).max_by(|&a, &b| self.magnitudes[a].total_cmp(&self.magnitudes[b]))?;
+            Some((self.bin_frequency(bin as f32 + self.interpolation.offset(&self.magnitudes, bin)), bin))
+        };
+        let nyquist = self.bin_frequency(last_bin as f32);
+        let peaks: Vec<(f32, usize)> = (1..=count)
+            .take_while(|&number| number as f32 * fundamental <= nyquist)
+            .filter_map(peak)
+            .collect();
+        let Some(&(_, fundamental_bin)) = peaks.first() else {
+            return Vec::new();
+        };
+        // Compare the heights of the peaks between bins, so the levels don't depend on how far
+        // each harmonic falls from a bin
+        let reference = peak_log_magnitude(&self.magnitudes, fundamental_bin);
+        peaks.iter()
+            .enumerate()
+            .map(|(index, &(frequency, bin))| Harmonic {
+                number: index + 1,
+                frequency,
+                level_db: 20.0 * (peak_log_magnitude(&self.magnitudes, bin) - reference) / LN_10,
+            })
+            .collect()
+    }
+
+    /// The closest note with A4 tuned to `a4_hz`, named in `notation`, or `None` if the dominant
+    /// frequency is outside the searched range.
+    pub fn note(&self, a4_hz: f32, notation: Notation) -> Option<NoteResult> {
+        let frequency = self.frequency.filter(|f| (self.min_freq..=self.max_freq).contains(f))?;
+        let midi = frequency_to_midi(frequency, a4_hz);
+        let cents = 1200.0 * (frequency / midi_to_frequency(midi, a4_hz)).log2();
+        // The FFT's own peak, or the bin the other methods' estimate falls in
+        let bin = match self.method {
+            Method::Fft => self.max_index,
+            _ => Some((frequency / self.bin_frequency(1.0)).round() as usize),
+        };
+        // A range holding no bins has no peak to measure
+        let search_bins = self.search_bins();
+        let searched = self.magnitudes.get(search_bins.clone());
+        let confidence = match (searched, bin.and_then(|bin| bin.checked_sub(*search_bins.start()))) {
+            (Some(searched), Some(peak)) => peak_confidence(searched, peak),
+            _ => 0.0,
+        };
+        Some(NoteResult {
+            frequency,
+            note: notation.note_name(midi),
+            midi,
+            cents,
+            confidence,
+            rms: self.rms,
+            dbfs: to_dbfs(self.rms),
+        })
+    }
+}
+
+/// Decoded audio with its channels still interleaved.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Audio {
+    /// Interleaved `f32` samples in `[-1.0, 1.0]`.
+    pub samples: Vec<f32>,
+    /// Number of interleaved channels.
+    pub channels: usize,
+    /// Sample rate in Hz.
+    pub sample_rate: u32,
+}
+
+impl Audio {
+    /// All channels averaged to mono.
+    pub fn mono(&self) -> Vec<f32> {
+        downmix(&self.samples, self.channels)
+    }
+
+    /// The samples of a single channel (0-based), or `None` if the audio has no such channel.
+    ///
+    /// A trailing partial frame, as left by a truncated file, adds a sample to the channels it
+    /// reaches, so those are one longer, like the [`mono`](Self::mono) mix, than the others.
+    pub fn channel(&self, channel: usize) -> Option<Vec<f32>> {
+        if channel >= self.channels.max(1) {
+            return None;
+        }
+        Some(self.samples.iter().skip(channel).step_by(self.channels.max(1)).cloned().collect())
+    }
+}
+
+/// Load a WAV, FLAC or Ogg Vorbis file as mono `f32` samples in `[-1.0, 1.0]`, returning them with
+/// the sample rate.
+///
+/// Files with any number of channels, such as 5.1 surround captures, are [`downmix`]ed.
+pub fn load_wav(path: &Path) -> Result<(Vec<f32>, u32), Error> {
+    let audio = read_audio(path)?;
+    Ok((audio.mono(), audio.sample_rate))
+}
+
+/// Read a WAV or, for a `.flac` or `.ogg` extension, FLAC or Ogg Vorbis file without downmixing it.
+pub fn read_audio(path: &Path) -> Result<Audio, Error> {
+    match path.extension() {
+        Some(ext) if ext.eq_ignore_ascii_case("flac") => read_flac(path),
+        Some(ext) if ext.eq_ignore_ascii_case("ogg") => read_ogg(path),
+        _ => read_wav(path),
+    }
+}
+
+/// Read a WAV file without downmixing it.
+///
+/// Both plain and `WAVE_FORMAT_EXTENSIBLE` headers are accepted, including integer samples stored
+/// in a wider container, such as 24 valid bits in 32.
+pub fn read_wav(path: &Path) -> Result<Audio, Error> {
+    let file = File::open(path).map_err(Error::FileOpen)?;
+    decode_wav(BufReader::new(file))
+}
+
+/// Read a WAV stream, such as stdin, without downmixing it.
+///
+/// The stream must be a complete WAV file, starting with a readable header; raw PCM is not
+/// accepted.
+pub fn read_wav_from<R: Read>(reader: R) -> Result<Audio, Error> {
+    decode_wav(reader)
+}
+
+// Collect every sample of a WAV stream as normalized f32
+fn decode_wav<R: Read>(mut stream: R) -> Result<Audio, Error> {
+    let mut bytes = Vec::new();
+    stream.read_to_end(&mut bytes).map_err(Error::FileOpen)?;
+
+    // WAVE_FORMAT_EXTENSIBLE files may store fewer valid bits than their container holds, such as
+    // 24-bit samples in 32 bits. The valid bits are left-aligned, but hound reads them from the
+    // low end of the container, so such integer data is decoded here instead.
+    let header = hound::WavReader::new(Cursor::new(&bytes[..]))?;
+    let spec = header.spec();
+    let sample_count = header.len() as usize;
+    if spec.sample_format == hound::SampleFormat::Int && sample_count > 0 {
+        // The reader stops at the start of the data chunk, right after the chunk's length field
+        let data_start = header.into_inner().position() as usize;
+        let length_field = bytes[data_start - 4..data_start].try_into().expect("slice is 4 bytes");
+        let width = u32::from_le_bytes(length_field) as usize / sample_count;
+        if (1..=4).contains(&width) && spec.bits_per_sample < width as u16 * 8 {
+            let data = &bytes[data_start..(data_start + sample_count * width).min(bytes.len())];
+            return audio_from(spec, decode_left_aligned(data, width));
+        }
+    }
+    let mut reader = hound::WavReader::new(Cursor::new(bytes))?;
+
+    // Collect samples based on the bit depth or format
+    let samples: Vec<f32> = match spec.sample_format {
+        hound::SampleFormat::Int => {
+            match spec.bits_per_sample {
+                // 8-bit WAV is unsigned with its midpoint at 128; hound hands it over as i8
+                // already recentred, i.e. `s - 128`
+                8 => reader.samples::<i8>()
+                    .map(|s| s.map(|s| s as f32 / 128.0))
+                    .collect::<Result<Vec<f32>, _>>()?,
+                16 => reader.samples::<i16>()
+                    .map(|s| s.map(|s| (s as f32) / i16::MAX as f32))
+                    .collect::<Result<Vec<f32>, _>>()?,
+                bits => {
+                    let scale = int_scale(bits)
+                        .ok_or(Error::UnsupportedBitDepth { bits, format: "integer" })?;
+                    reader.samples::<i32>()
+                        .map(|s| s.map(|s| (s as f32) / scale))
+                        .collect::<Result<Vec<f32>, _>>()?
+                }
+            }
+        },
+        hound::SampleFormat::Float => {
+            match spec.bits_per_sample {
+                32 => reader.samples::<f32>()
+                    .collect::<Result<Vec<f32>, _>>()?,
+                bits => return Err(Error::UnsupportedBitDepth { bits, format: "float" }),
+            }
+        },
+    };
+    audio_from(spec, samples)
+}
+
+// Integer samples whose valid bits are left-aligned in little-endian containers of `width` bytes,
+// normalized to [-1.0, 1.0]. A trailing partial container is dropped.
+fn decode_left_aligned(data: &[u8], width: usize) -> Vec<f32> {
+    data.chunks_exact(width)
+        .map(|container| {
+            // Place the container in the top bytes of an i32, so every width shares one scale
+            let mut word = [0u8; 4];
+            word[4 - width..].copy_from_slice(container);
+            i32::from_le_bytes(word) as f32 / (1u64 << 31) as f32
+        })
+        .collect()
+}
+
+fn audio_from(spec: hound::WavSpec, samples: Vec<f32>) -> Result<Audio, Error> {
+    if samples.is_empty() {
+        return Err(Error::EmptyFile);
+    }
+
+    Ok(Audio {
+        samples,
+        channels: spec.channels as usize,
+        sample_rate: spec.sample_rate,
+    })
+}
+
+// Divisor that maps integer samples of the given bit depth to [-1.0, 1.0], or `None` if the
+// depth isn't supported
+fn int_scale(bits: u16) -> Option<f32> {
+    match bits {
+        16 => Some(i16::MAX as f32),
+        24 => Some((1 << 23) as f32),
+        _ => None,
+    }
+}
+
+/// Detect the dominant note in a complete WAV file held in memory, header included, loaded and
+/// analyzed exactly like a file on disk with the default [`WavAnalyzer`].
+pub fn detect_note_from_bytes(bytes: &[u8]) -> Result<NoteResult, Error> {
+    let audio = read_wav_from(Cursor::new(bytes))?;
+    detect_note(&audio.mono(), audio.sample_rate)?.ok_or(Error::NoPitch)
+}
+
+/// Combine interleaved channels to mono by averaging all `channels` samples of each frame.
+///
+/// A trailing partial frame, as left by a truncated file, is averaged over the samples present.
+pub fn downmix(samples: &[f32], channels: usize) -> Vec<f32> {
+    match channels {
+        0 | 1 => samples.to_vec(),
+        _ => samples.chunks(channels)
+            .map(|frame| frame.iter().sum::<f32>() / frame.len() as f32)
+            .collect(),
+    }
+}
+
+/// Detect the dominant note in mono samples, or `None` if it falls outside the expected range.
+pub fn detect_note(samples: &[f32], sample_rate: u32) -> Result<Option<NoteResult>, Error> {
+    WavAnalyzer::new().detect(samples, sample_rate)
+}
+
+/// Low-pass filter and decimate mono samples by `factor`, returning them with the reduced rate.
+///
+/// A factor of 1 (or 0) returns the samples unchanged.
+pub fn downsample(samples: &[f32], sample_rate: u32, factor: usize) -> (Vec<f32>, f32) {
+    let downsample_factor = factor.max(1);
+    let downsampled_sample_rate = sample_rate as f32 / downsample_factor as f32;
+
+    // Low-pass below the new Nyquist frequency first so higher content doesn't alias
+    let filtered_samples = lowpass(samples, downsampled_sample_rate / 2.0, sample_rate);
+    let downsampled_samples: Vec<f32> = filtered_samples.into_iter().step_by(downsample_factor).collect();
+
+    (downsampled_samples, downsampled_sample_rate)
+}
+
+/// Run the full pitch-detection pipeline on mono samples.
+///
+/// Fails with [`Error::TooShort`] if the requested excerpt holds fewer than
+/// [`MIN_ANALYSIS_SAMPLES`] samples after downsampling, with [`Error::ZeroTargetRate`] for a
+/// `target_rate` of 0, with [`Error::FftSizeTooSmall`] for an `fft_size` below 2, and with
+/// [`Error::InvalidWindowSigma`] for a [`Window::Gaussian`] whose sigma isn't finite and above 0.
+pub fn analyze(samples: &[f32], sample_rate: u32, settings: &Settings) -> Result<Analysis, Error> {
+    analyze_with(samples, sample_rate, settings, &FftCache::default())
+}
+
+// `analyze`, taking the FFT plan from `plans`
+fn analyze_with(
+    samples: &[f32],
+    sample_rate: u32,
+    settings: &Settings,
+    plans: &FftCache,
+) -> Result<Analysis, Error> {
+    if settings.target_rate == Some(0) {
+        return Err(Error::ZeroTargetRate);
+    }
+    if let Some(size @ 0..=1) = settings.fft_size {
+        return Err(Error::FftSizeTooSmall { size });
+    }
+    if let Window::Gaussian(sigma) = settings.window {
+        if !(sigma > 0.0 && sigma.is_finite()) {
+            return Err(Error::InvalidWindowSigma { sigma });
+        }
+    }
+    let (downsampled_samples, downsampled_sample_rate) = settings.convert_rate(samples, sample_rate);
+
+    // Use only the requested excerpt
+    let mut limited_samples = settings.excerpt(&downsampled_samples, downsampled_sample_rate)?.to_vec();
+    if limited_samples.len() < MIN_ANALYSIS_SAMPLES {
+        return Err(Error::TooShort { samples: limited_samples.len(), required: MIN_ANALYSIS_SAMPLES });
+    }
+    if settings.remove_dc {
+        remove_dc(&mut limited_samples);
+    }
+    let level = rms(&limited_samples);
+    match settings.emphasis {
+        Some(Emphasis::Pre(coeff)) => limited_samples = pre_emphasis(&limited_samples, coeff),
+        Some(Emphasis::De(coeff)) => limited_samples = de_emphasis(&limited_samples, coeff),
+        None => {}
+    }
+    if settings.normalize {
+        normalize(&mut limited_samples);
+    }
+
+    // Split the excerpt into half-overlapping frames, a single one spanning it by default, and
+    // average their magnitude spectra (Welch's method): noise varies from frame to frame while a
+    // sustained tone doesn't, so the average jitters less. Frames never get shorter than
+    // MIN_ANALYSIS_SAMPLES; the hop shrinks instead.
+    let frames = settings.average_frames.max(1);
+    let window_size = (2 * limited_samples.len() / (frames + 1)).max(MIN_ANALYSIS_SAMPLES);
+
+    // Zero-pad to the next power of two, which is fast to transform and gives finer bin spacing,
+    // unless the FFT size is set; frames longer than that are cut to it before windowing
+    let fft_size = settings.fft_size.unwrap_or(window_size.next_power_of_two());
+    let window_size = window_size.min(fft_size);
+    let hop = (limited_samples.len() - window_size) / (frames - 1).max(1);
+
+    let fft = plans.forward(fft_size);
+    let mut magnitudes = average_spectrum(&limited_samples, window_size, hop, frames, settings.window, fft.as_ref());
+    let hz_per_bin = downsampled_sample_rate / fft_size as f32;
+
+    // Above the crossover, use frames MULTIRES_RATIO times shorter instead, zero-padded to the
+    // same FFT size so their bins line up: a short frame gathers an overtone that wavers in pitch
+    // into one peak where the long frame smears it over many bins. Scaling by the length ratio
+    // keeps a steady tone's peak at the same height in both.
+    if settings.multires {
+        let short_size = (window_size / MULTIRES_RATIO).max(MIN_ANALYSIS_SAMPLES);
+        let short_frames = (limited_samples.len() - short_size) / (short_size / 2) + 1;
+        let short = average_spectrum(
+            &limited_samples,
+            short_size,
+            short_size / 2,
+            short_frames,
+            settings.window,
+            fft.as_ref(),
+        );
+        let scale = window_size as f32 / short_size as f32;
+        let crossover = ((MULTIRES_CROSSOVER_HZ / hz_per_bin).ceil() as usize).min(magnitudes.len());
+        for (magnitude, &short) in magnitudes[crossover..].iter_mut().zip(&short[crossover..]) {
+            *magnitude = short * scale;
+        }
+    }
+
+    if let Some(band) = &settings.band {
+        for (bin, magnitude) in magnitudes.iter_mut().enumerate() {
+            if !band.contains(&(bin as f32 * hz_per_bin)) {
+                *magnitude = 0.0;
+            }
+        }
+    }
+
+    // Find the index of the maximum magnitude (dominant frequency) among the bins in the search
+    // range, on the smoothed spectrum if asked; the strongest raw bin within half the smoothing
+    // width of it is the one refined below
+    let search_bins = bin_range(settings.min_freq, settings.max_freq, hz_per_bin, magnitudes.len());
+    let smoothed = (settings.smooth_spectrum > 1).then(|| moving_average(&magnitudes, settings.smooth_spectrum));
+    let searched = smoothed.as_deref().unwrap_or(&magnitudes);
+    let peak_index = searched.iter()
+        .enumerate()
+        .take(search_bins.end() + 1)
+        .skip(*search_bins.start())
+        .max_by(|a, b| a.1.total_cmp(b.1))
+        .map(|(index, _)| {
+            if settings.fix_octave {
+                fix_octave(searched, index, *search_bins.start())
+            } else {
+                index
+            }
+        })
+        .map(|index| {
+            let half = settings.smooth_spectrum / 2;
+            let nearby = index.saturating_sub(half).max(*search_bins.start())
+                ..=(index + half).min(*search_bins.end());
+            nearby.max_by(|&a, &b| magnitudes[a].total_cmp(&magnitudes[b])).unwrap_or(index)
+        });
+
+    // Refine the peak position between bins, then calculate the dominant frequency in Hz
+    let bin_to_hz = |index: usize| {
+        let peak_bin = index as f32 + settings.interpolation.offset(&magnitudes, index);
+        peak_bin * hz_per_bin
+    };
+    let frequency = match settings.method {
+        Method::Fft => peak_index.map(bin_to_hz),
+        Method::Hps => hps_peak(&magnitudes, HPS_HARMONICS, search_bins.clone()).map(bin_to_hz),
+        Method::Autocorr => autocorrelation_pitch(&limited_samples, downsampled_sample_rate),
+        Method::Yin => yin_pitch(&limited_samples, downsampled_sample_rate, YIN_THRESHOLD),
+        Method::Cqt => {
+            let (min_freq, max_freq) = (settings.min_freq, settings.max_freq);
+            cqt_pitch(&limited_samples, downsampled_sample_rate, min_freq, max_freq, settings.interpolation)
+        }
+        Method::Cepstrum => {
+            let (min_freq, max_freq) = (settings.min_freq, settings.max_freq);
+            cepstrum_pitch_with(&limited_samples, downsampled_sample_rate, min_freq, max_freq, plans)
+        }
+    };
+    let frequency = if settings.precise {
+        frequency.map(|coarse| {
+            instantaneous_frequency(&limited_samples, downsampled_sample_rate, coarse).unwrap_or(coarse)
+        })
+    } else {
+        frequency
+    };
+
+    Ok(Analysis {
+        sample_rate: downsampled_sample_rate,
+        window_size,
+        fft_size,
+        magnitudes,
+        max_index: peak_index,
+        frequency,
+        min_freq: settings.min_freq,
+        max_freq: settings.max_freq,
+        rms: level,
+        interpolation: settings.interpolation,
+        method: settings.method,
+    })
+}
+
+// Mean magnitude spectrum of `frames` frames of `frame_size` samples starting every `hop` samples,
+// each windowed and zero-padded to the FFT's length
+fn average_spectrum(
+    samples: &[f32],
+    frame_size: usize,
+    hop: usize,
+    frames: usize,
+    window: Window,
+    fft: &dyn RealToComplex<f32>,
+) -> Vec<f32> {
+    // The real-input FFT yields only the non-negative half of the spectrum (fft_size / 2 + 1 bins)
+    let mut magnitudes = vec![0.0; fft.len() / 2 + 1];
+    let mut spectrum = fft.make_output_vec();
+    for frame in 0..frames {
+        // Apply the window function to reduce spectral leakage
+        let start = frame * hop;
+        let (mut buffer, gain) = apply_window(&samples[start..start + frame_size], window);
+        buffer.resize(fft.len(), 0.0);
+
+        // Apply the FFT
+        fft.process(&mut buffer, &mut spectrum).expect("FFT buffer lengths don't match the plan");
+
+        // Accumulate the magnitudes of the FFT result, undoing the window's attenuation so they
+        // read the same whichever window is chosen
+        for (magnitude, c) in magnitudes.iter_mut().zip(&spectrum) {
+            *magnitude += c.norm() / gain / frames as f32;
+        }
+    }
+    magnitudes
+}
+
+/// Root-mean-square level of samples, 0.0 for no samples.
+pub fn rms(samples: &[f32]) -> f32 {
+    if samples.is_empty() {
+        return 0.0;
+    }
+    (samples.iter().map(|s| s * s).sum::<f32>() / samples.len() as f32).sqrt()
+}
+
+/// Convert a linear level, where 1.0 is full scale, to decibels relative to full scale.
+///
+/// Silence maps to negative infinity.
+pub fn to_dbfs(level: f32) -> f32 {
+    20.0 * level.log10()
+}
+
+/// Index of the first sample of the first 10 ms block whose RMS level exceeds `threshold_dbfs`,
+/// or `None` if the whole recording stays below it.
+pub fn first_sound(samples: &[f32], sample_rate: f32, threshold_dbfs: f32) -> Option<usize> {
+    let block = ((sample_rate / 100.0) as usize).max(1);
+    samples.chunks(block)
+        .position(|chunk| to_dbfs(rms(chunk)) > threshold_dbfs)
+        .map(|index| index * block)
+}
+
+/// Subtract the mean from samples in place, removing any DC offset.
+pub fn remove_dc(samples: &mut [f32]) {
+    if samples.is_empty() {
+        return;
+    }
+    let mean = samples.iter().sum::<f32>() / samples.len() as f32;
+    samples.iter_mut().for_each(|s| *s -= mean);
+}
+
+/// First-order pre-emphasis, `y[n] = x[n] - coeff * x[n - 1]`, which boosts high frequencies
+/// relative to low ones. A `coeff` around 0.97 is typical.
+pub fn pre_emphasis(samples: &[f32], coeff: f32) -> Vec<f32> {
+    let mut previous = 0.0;
+    samples.iter()
+        .map(|&sample| {
+            let output = sample - coeff * previous;
+            previous = sample;
+            output
+        })
+        .collect()
+}
+
+/// First-order de-emphasis, `y[n] = x[n] + coeff * y[n - 1]`, the inverse of [`pre_emphasis`],
+/// which boosts low frequencies such as a weak fundamental relative to its harmonics.
+pub fn de_emphasis(samples: &[f32], coeff: f32) -> Vec<f32> {
+    let mut previous = 0.0;
+    samples.iter()
+        .map(|&sample| {
+            previous = sample + coeff * previous;
+            previous
+        })
+        .collect()
+}
+
+/// Scale samples in place so the largest absolute value is 1.0. Silence is left untouched.
+pub fn normalize(samples: &mut [f32]) {
+    let peak = samples.iter().fold(0.0f32, |peak, s| peak.max(s.abs()));
+    if peak > 0.0 {
+        samples.iter_mut().for_each(|s| *s /= peak);
+    }
+}
+
+/// Fraction of samples, from 0.0 to 1.0, that lie in runs of at least three consecutive samples at
+/// or near full scale (±1.0).
+///
+/// Clipping flattens the waveform's peaks into such runs, which adds strong harmonics that
+/// don't belong to the recording.
+pub fn clipping_ratio(samples: &[f32]) -> f32 {
+    if samples.is_empty() {
+        return 0.0;
+    }
+    let clipped: usize = samples.chunk_by(|a, b| (a.abs() >= CLIP_LEVEL) == (b.abs() >= CLIP_LEVEL))
+        .filter(|run| run.len() >= MIN_CLIP_RUN && run[0].abs() >= CLIP_LEVEL)
+        .map(<[f32]>::len)
+        .sum();
+    clipped as f32 / samples.len() as f32
+}
+
+// Bins whose frequency lies in [min_freq, max_freq], clamped to a spectrum of `bins` bins
+fn bin_range(min_freq: f32, max_freq: f32, hz_per_bin: f32, bins: usize) -> RangeInclusive<usize> {
+    let low = (min_freq / hz_per_bin).ceil() as usize;
+    let high = ((max_freq / hz_per_bin).floor() as usize).min(bins.saturating_sub(1));
+    low..=high
+}
+
+/// Find the fundamental's bin with the Harmonic Product Spectrum.
+///
+/// The spectrum is downsampled by every factor in `2..=harmonics` and multiplied element-wise
+/// with itself, so the bin whose harmonics all carry energy wins even when an overtone is louder
+/// than the fundamental. Only fundamentals in `bins` are considered, leaving out the DC bin and
+/// any whose highest harmonic lies past the end of the spectrum; returns `None` if that leaves
+/// none.
+pub fn hps_peak(magnitudes: &[f32], harmonics: usize, bins: RangeInclusive<usize>) -> Option<usize> {
+    let harmonics = harmonics.max(1);
+    let length = magnitudes.len().saturating_sub(harmonics / 2) / harmonics;
+
+    // Downsampling takes the maximum of the h bins around bin * h, since the true harmonic of a
+    // fundamental that lies between bins can land up to h / 2 bins away from bin * h
+    let downsampled = |bin: usize, h: usize| {
+        magnitudes[bin * h - h / 2..=bin * h + h / 2].iter().cloned().fold(0.0, f32::max)
+    };
+
+    bins.filter(|bin| (1..length).contains(bin))
+        .map(|bin| {
+            let product: f32 = (1..=harmonics).map(|h| downsampled(bin, h)).product();
+            (bin, product)
+        })
+        .max_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(bin, _)| bin)
+}
+
+/// Follow the spectral peak at bin `peak` down by octaves while the bin at half its frequency is a
+/// subharmonic reaching [`SUBHARMONIC_THRESHOLD`] of the peak's magnitude, stopping above `min_bin`.
+///
+/// A tone whose second harmonic is louder than its fundamental peaks an octave too high; the
+/// fundamental still shows up at half the frequency, while a lone tone has nothing there.
+pub fn fix_octave(magnitudes: &[f32], peak: usize, min_bin: usize) -> usize {
+    let mut fundamental = peak;
+    loop {
+        // Half a peak between bins can fall a bin either side of fundamental / 2
+        let half = fundamental / 2;
+        let low = half.saturating_sub(1).max(min_bin.max(1));
+        let candidates = low..=(half + 1).min(fundamental.saturating_sub(1));
+        let Some(best) = candidates.max_by(|&a, &b| magnitudes[a].total_cmp(&magnitudes[b])) else {
+            return fundamental;
+        };
+        let is_local_max = magnitudes[best] >= magnitudes[best - 1] && magnitudes[best] >= magnitudes[best + 1];
+        if !is_local_max || magnitudes[best] < SUBHARMONIC_THRESHOLD * magnitudes[peak] {
+            return fundamental;
+        }
+        fundamental = best;
+    }
+}
+
+/// Estimate the fundamental from the first strong autocorrelation peak after zero lag.
+///
+/// Returns `None` for silent input or when no periodicity between 20 Hz and 4,000 Hz is found.
+pub fn autocorrelation_pitch(samples: &[f32], sample_rate: f32) -> Option<f32> {
+    let min_lag = ((sample_rate / 4000.0).floor() as usize).max(1);
+    let max_lag = ((sample_rate / 20.0).ceil() as usize).min(samples.len().saturating_sub(1));
+    if min_lag >= max_lag {
+        return None;
+    }
+
+    // Autocorrelation normalized by the zero-lag energy, so a perfectly periodic signal peaks at 1.0
+    let energy: f32 = samples.iter().map(|s| s * s).sum();
+    if energy == 0.0 {
+        return None;
+    }
+    let correlation: Vec<f32> = (0..=max_lag + 1)
+        .map(|lag| {
+            let sum: f32 = samples.iter().zip(&samples[lag.min(samples.len())..]).map(|(a, b)| a * b).sum();
+            sum / energy
+        })
+        .collect();
+
+    // Skip past the zero-lag lobe, then take the first local maximum close to the strongest one;
+    // later peaks at multiples of the period would report a lower octave
+    let start = (min_lag..max_lag).find(|&lag| correlation[lag] < 0.0)?;
+    let strongest = correlation[start..=max_lag].iter().cloned().fold(f32::MIN, f32::max);
+    if strongest <= 0.0 {
+        return None;
+    }
+    let lag = (start..=max_lag).find(|&lag| {
+        correlation[lag] >= 0.9 * strongest
+            && correlation[lag] >= correlation[lag - 1]
+            && correlation[lag] >= correlation[lag + 1]
+    })?;
+
+    let period = lag as f32 + interpolate_peak(&correlation, lag);
+    Some(sample_rate / period)
+}
+
+/// Estimate the fundamental with the YIN algorithm (de Cheveigné & Kawahara, 2002).
+///
+/// `threshold` is the absolute threshold on the cumulative mean normalized difference; lower
+/// values are stricter. Returns `None` when no lag between 20 Hz and 4,000 Hz dips below it.
+pub fn yin_pitch(samples: &[f32], sample_rate: f32, threshold: f32) -> Option<f32> {
+    // Compare the first half of the buffer against lagged copies of itself
+    let window = samples.len() / 2;
+    let min_lag = ((sample_rate / 4000.0).floor() as usize).max(2);
+    let max_lag = ((sample_rate / 20.0).ceil() as usize).min(window);
+    if min_lag + 1 >= max_lag {
+        return None;
+    }
+
+    // Difference function d(tau)
+    let difference: Vec<f32> = (0..=max_lag)
+        .map(|lag| {
+            samples[..window].iter()
+                .zip(&samples[lag..lag + window])
+                .map(|(a, b)| (a - b) * (a - b))
+                .sum()
+        })
+        .collect();
+
+    // Cumulative mean normalized difference d'(tau), which starts at 1 and dips at the period
+    let mut normalized = vec![1.0; max_lag + 1];
+    let mut running_sum = 0.0;
+    for lag in 1..=max_lag {
+        running_sum += difference[lag];
+        normalized[lag] = if running_sum == 0.0 { 1.0 } else { difference[lag] * lag as f32 / running_sum };
+    }
+
+    // Absolute threshold: the first dip below it, followed down to its local minimum
+    let mut lag = (min_lag..max_lag).find(|&lag| normalized[lag] < threshold)?;
+    while lag + 1 < max_lag && normalized[lag + 1] < normalized[lag] {
+        lag += 1;
+    }
+
+    let period = lag as f32 + parabolic_offset(normalized[lag - 1], normalized[lag], normalized[lag + 1]);
+    Some(sample_rate / period)
+}
+
+/// Fraction of the spectral energy above the noise floor in `magnitudes` that lies within two
+/// bins of `peak`.
+///
+/// A clean tone scores close to 1.0, while several competing tones score low. Each bin's share of
+/// the [`noise_floor`] is left out, so steady background noise doesn't dilute a clear peak, and a
+/// peak that doesn't exceed the floor by [`NOISE_FLOOR_FACTOR`] scores 0.0.
+pub fn peak_confidence(magnitudes: &[f32], peak: usize) -> f32 {
+    let floor = noise_floor(magnitudes);
+    if magnitudes.get(peak).is_none_or(|&magnitude| magnitude <= floor * NOISE_FLOOR_FACTOR) {
+        return 0.0;
+    }
+    let energy = |bins: &[f32]| bins.iter().map(|m| (m - floor).max(0.0).powi(2)).sum::<f32>();
+    energy(&magnitudes[peak.saturating_sub(2)..(peak + 3).min(magnitudes.len())]) / energy(magnitudes)
+}
+
+/// Magnitude-weighted mean frequency of a spectrum in Hz, a measure of brightness; 0.0 for silence.
+///
+/// `magnitudes` is the non-negative half of an `fft_size`-point FFT of audio at `sample_rate`.
+pub fn spectral_centroid(magnitudes: &[f32], sample_rate: f32, fft_size: usize) -> f32 {
+    let hz_per_bin = sample_rate / fft_size as f32;
+    let total: f32 = magnitudes.iter().sum();
+    if total == 0.0 {
+        return 0.0;
+    }
+    let weighted: f32 = magnitudes.iter().enumerate().map(|(bin, m)| bin as f32 * hz_per_bin * m).sum();
+    weighted / total
+}
+
+/// Magnitudes in decibels relative to the strongest, `20 * log10(magnitude / max)`, so the peak is
+/// at 0 dB and everything else is negative.
+///
+/// Zero magnitudes, and every bin of an all-zero spectrum, map to negative infinity.
+pub fn magnitudes_db(magnitudes: &[f32]) -> Vec<f32> {
+    let max = magnitudes.iter().cloned().fold(0.0, f32::max);
+    magnitudes.iter()
+        .map(|&magnitude| if max > 0.0 { to_dbfs(magnitude / max) } else { f32::NEG_INFINITY })
+        .collect()
+}
+
+// Fit a parabola through the log-magnitudes around a peak and return its fractional bin offset
+fn interpolate_peak(magnitudes: &[f32], index: usize) -> f32 {
+    Interpolation::Gaussian.offset(magnitudes, index)
+}
+
+// Natural log of a peak's height between bins: the vertex of the parabola through the log
+// magnitudes around `bin`, which like `interp_gaussian` fits a windowed tone's main lobe closely
+fn peak_log_magnitude(magnitudes: &[f32], bin: usize) -> f32 {
+    let ln = |bin: usize| magnitudes[bin].max(f32::MIN_POSITIVE).ln();
+    if bin == 0 || bin + 1 >= magnitudes.len() {
+        return ln(bin);
+    }
+    let (left, center, right) = (ln(bin - 1), ln(bin), ln(bin + 1));
+    center - 0.25 * (left - right) * parabolic_offset(left, center, right)
+}
+
+// Vertex offset of the parabola through three equally spaced points, for a maximum or a minimum
+fn parabolic_offset(left: f32, center: f32, right: f32) -> f32 {
+    let denominator = left - 2.0 * center + right;
+    if denominator == 0.0 {
+        return 0.0;
+    }
+    (0.5 * (left - right) / denominator).clamp(-0.5, 0.5)
+}
+
+// Windowed-sinc low-pass filter, used as the anti-aliasing step before decimation
+fn lowpass(samples: &[f32], cutoff_hz: f32, sample_rate: u32) -> Vec<f32> {
+    let Some(kernel) = lowpass_kernel(cutoff_hz, sample_rate) else {
+        return samples.to_vec(); // Nothing above Nyquist to remove
+    };
+
+    // Convolve, keeping the output aligned with the input
+    let half = kernel.len() / 2;
+    (0..samples.len())
+        .map(|i| {
+            kernel.iter()
+                .enumerate()
+                .filter_map(|(k, &coeff)| {
+                    (i + k).checked_sub(half)
+                        .and_then(|j| samples.get(j))
+                        .map(|&sample| sample * coeff)
+                })
+                .sum()
+        })
+        .collect()
+}
+
+// Hann-windowed sinc kernel of `lowpass`, with an odd number of taps and unity gain at DC, or
+// `None` if the cutoff is at or above Nyquist
+fn lowpass_kernel(cutoff_hz: f32, sample_rate: u32) -> Option<Vec<f32>> {
+    let cutoff = cutoff_hz / sample_rate as f32; // Cutoff in cycles per sample
+    if cutoff >= 0.5 {
+        return None;
+    }
+
+    let taps = 101;
+    let center = (taps / 2) as f32;
+    let mut kernel: Vec<f32> = (0..taps)
+        .map(|n| {
+            let m = n as f32 - center;
+            let sinc = if m == 0.0 {
+                2.0 * cutoff
+            } else {
+                (2.0 * PI * cutoff * m).sin() / (PI * m)
+            };
+            sinc * Window::Hann.value(n, taps)
+        })
+        .collect();
+    let gain: f32 = kernel.iter().sum();
+    kernel.iter_mut().for_each(|k| *k /= gain);
+    Some(kernel)
+}
+
+/// Convert a frequency to the nearest MIDI note number (A4 = 69), with A4 tuned to `a4_hz`.
+pub fn frequency_to_midi(frequency: f32, a4_hz: f32) -> i32 {
+    let note_number = 12.0 * (frequency / a4_hz).log2() + 69.0; // Use MIDI note number for A4 = 69
+    note_number.round() as i32
+}
+
+/// Exact frequency in Hz of a MIDI note number, with A4 tuned to `a4_hz`.
+pub fn midi_to_frequency(midi: i32, a4_hz: f32) -> f32 {
+    a4_hz * 2f32.powf((midi - 69) as f32 / 12.0)
+}
+
+/// Convert a frequency to the name of the nearest note, e.g. `A4`, with A4 tuned to `a4_hz`.
+pub fn frequency_to_note_name(frequency: f32, a4_hz: f32) -> String {
+    midi_to_note_name(frequency_to_midi(frequency, a4_hz))
+}
+
+/// Name of a MIDI note number with sharps, e.g. `A4` for 69 (see [`Notation::note_name`]).
+pub fn midi_to_note_name(rounded_note_number: i32) -> String {
+    Notation::EnglishSharp.note_name(rounded_note_number)
+}
+
+/// Name of a MIDI note number's pitch class with sharps, without the octave, e.g. `A` for 69.
+pub fn pitch_class_name(midi: i32) -> &'static str {
+    Notation::EnglishSharp.pitch_class(midi)
+}
+
+/// Convert a frequency to the name of the nearest note and its signed deviation in cents, with A4
+/// tuned to `a4_hz`.
+pub fn frequency_to_note_cents(frequency: f32, a4_hz: f32) -> (String, f32) {
+    let midi = frequency_to_midi(frequency, a4_hz);
+
+    let ideal_freq = midi_to_frequency(midi, a4_hz);
+    let cents = 1200.0 * (frequency / ideal_freq).log2();
+
+    (frequency_to_note_name(frequency, a4_hz), cents)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{float_wav, mix, sine, wav, Noise};
+
+    #[test]
+    fn names_reference_pitches() {
+        assert_eq!(frequency_to_note_name(440.0, A4_HZ), "A4");
+        assert_eq!(frequency_to_note_name(261.63, A4_HZ), "C4");
+        assert_eq!(frequency_to_note_name(16.35, A4_HZ), "C0");
+    }
+
+    #[test]
+    fn rounds_to_the_nearest_note() {
+        // A#4 is 466.16 Hz; the quarter-tone between A4 and A#4 lies at 452.89 Hz
+        assert_eq!(frequency_to_note_name(466.0, A4_HZ), "A#4");
+        assert_eq!(frequency_to_note_name(452.0, A4_HZ), "A4");
+        assert_eq!(frequency_to_note_name(454.0, A4_HZ), "A#4");
+    }
+
+    #[test]
+    fn names_notes_below_c0() {
+        assert_eq!(frequency_to_note_name(15.5, A4_HZ), "B-1");
+        assert_eq!(midi_to_note_name(0), "C-1");
+        assert_eq!(midi_to_note_name(-1), "B-2");
+
+        // The whole pipeline, down to the name, once the search range reaches that low
+        let settings = Settings { downsample: 1, min_freq: 10.0, ..Settings::default() };
+        let analysis = analyze(&sine(15.4, 16_000, 2000), 2000, &settings).unwrap();
+        let note = analysis.note(A4_HZ, Notation::default()).unwrap();
+        assert_eq!(note.note, "B-1");
+    }
+
+    #[test]
+    fn fixes_a_peak_on_the_second_harmonic() {
+        // A 220 Hz tone whose second harmonic is the loudest partial
+        let samples = mix(&[(220.0, 0.4), (440.0, 1.0), (660.0, 0.3)], 16_384, 8000);
+        let settings = Settings { downsample: 1, ..Settings::default() };
+        let detect = |settings: &Settings| analyze(&samples, 8000, settings).unwrap().frequency.unwrap();
+        assert!((detect(&settings) - 440.0).abs() < 1.0);
+        assert!((detect(&Settings { fix_octave: true, ..settings.clone() }) - 220.0).abs() < 1.0);
+
+        // A lone tone stays where it is
+        let sine = sine(440.0, 16_384, 8000);
+        let analysis = analyze(&sine, 8000, &Settings { fix_octave: true, ..settings }).unwrap();
+        assert!((analysis.frequency.unwrap() - 440.0).abs() < 1.0);
+    }
+
+    #[test]
+    fn hps_keeps_c4_whose_second_harmonic_is_louder() {
+        // C4 with a second harmonic twice as loud and three weaker ones above it
+        let partials = [(261.63, 0.3), (523.25, 0.6), (784.88, 0.2), (1046.5, 0.15), (1308.13, 0.1)];
+        let samples = mix(&partials, 16_384, 8000);
+        let settings = Settings { downsample: 1, ..Settings::default() };
+        let note = |settings: &Settings| {
+            analyze(&samples, 8000, settings).unwrap().note(A4_HZ, Notation::default()).unwrap().note
+        };
+        assert_eq!(note(&settings), "C5");
+        assert_eq!(note(&Settings { method: Method::Hps, ..settings.clone() }), "C4");
+
+        // The product is only taken for fundamentals in the search range
+        let above = Settings { method: Method::Hps, min_freq: 400.0, ..settings.clone() };
+        assert!(analyze(&samples, 8000, &above).unwrap().frequency.unwrap() >= 400.0);
+        let magnitudes = &analyze(&samples, 8000, &settings).unwrap().magnitudes;
+        assert_eq!(hps_peak(magnitudes, HPS_HARMONICS, 0..=0), None);
+        assert_eq!(hps_peak(&magnitudes[..8], HPS_HARMONICS, 0..=7), None);
+    }
+
+    #[test]
+    fn autocorrelation_finds_a_fundamental_weaker_than_its_octave() {
+        // 220 Hz under a 440 Hz partial twice as loud, which the FFT peak reports instead
+        let samples = mix(&[(220.0, 0.3), (440.0, 0.6)], 8192, 8000);
+        let settings = Settings { downsample: 1, ..Settings::default() };
+        let fft = analyze(&samples, 8000, &settings).unwrap().frequency.unwrap();
+        assert!((fft - 440.0).abs() < 1.0, "{} Hz", fft);
+        let autocorr = autocorrelation_pitch(&samples, 8000.0).unwrap();
+        assert!((autocorr - 220.0).abs() < 1.0, "{} Hz", autocorr);
+        let method = Settings { method: Method::Autocorr, ..settings };
+        assert!((analyze(&samples, 8000, &method).unwrap().frequency.unwrap() - 220.0).abs() < 1.0);
+    }
+
+    #[test]
+    fn yin_finds_the_fundamental_of_a_sawtooth() {
+        // A band-limited sawtooth on C3, its harmonics falling off as 1/n
+        let partials: Vec<(f32, f32)> = (1..=20).map(|n| (130.81 * n as f32, 0.5 / n as f32)).collect();
+        let sawtooth = mix(&partials, 22_050, 44_100);
+        let frequency = yin_pitch(&sawtooth, 44_100.0, YIN_THRESHOLD).unwrap();
+        assert!((frequency - 130.81).abs() < 0.5, "{} Hz", frequency);
+
+        let settings = Settings { method: Method::Yin, ..Settings::default() };
+        let note = analyze(&sawtooth, 44_100, &settings).unwrap().note(A4_HZ, Notation::default()).unwrap();
+        assert_eq!(note.note, "C3");
+        assert_eq!(yin_pitch(&[0.0; 4096], 44_100.0, YIN_THRESHOLD), None);
+    }
+
+    #[test]
+    fn rates_the_confidence_at_the_estimate() {
+        let tone = mix(&[(440.0, 0.5)], 8192, 8000);
+        let settings = Settings { downsample: 1, ..Settings::default() };
+        for method in [Method::Fft, Method::Yin, Method::Autocorr] {
+            let analysis = analyze(&tone, 8000, &Settings { method, ..settings.clone() }).unwrap();
+            assert!(analysis.note(A4_HZ, Notation::default()).unwrap().confidence > 0.9, "{:?}", method);
+        }
+
+        // 16-point FFTs space bins 500 Hz apart, so 410-490 Hz holds none, though YIN's estimate
+        // of the tone falls in it
+        let narrow =
+            Settings { method: Method::Yin, fft_size: Some(16), min_freq: 410.0, max_freq: 490.0, ..settings };
+        let analysis = analyze(&tone, 8000, &narrow).unwrap();
+        assert!(analysis.search_bins().is_empty());
+        let note = analysis.note(A4_HZ, Notation::default()).unwrap();
+        assert_eq!((note.note.as_str(), note.confidence), ("A4", 0.0));
+    }
+
+    #[test]
+    fn counts_notes_but_not_their_overtones() {
+        // C3 with four overtones falling off as 1/n, against a plain E4
+        let mut partials: Vec<(f32, f32)> =
+            (1..=5).map(|harmonic| (130.81 * harmonic as f32, 0.3 / harmonic as f32)).collect();
+        partials.push((329.63, 0.3));
+        let samples = mix(&partials, 16_000, 8000);
+        let analysis = analyze(&samples, 8000, &Settings { downsample: 1, ..Settings::default() }).unwrap();
+        let chord = analysis.chord(A4_HZ, Notation::default(), POLY_THRESHOLD, NOISE_FLOOR_FACTOR, POLY_MAX_PEAKS);
+        assert_eq!(chord, ["C", "E", "G"]);
+        assert_eq!(analysis.polyphony(POLY_THRESHOLD, NOISE_FLOOR_FACTOR, POLY_MAX_PEAKS), 2);
+    }
+
+    #[test]
+    fn names_the_notes_of_a_c_major_triad() {
+        // C4, E4 and G4, each with overtones an octave and two octaves up; a twelfth would stand
+        // for another pitch class, which the chord lists like any other peak
+        let partials: Vec<(f32, f32)> = [261.63, 329.63, 392.0]
+            .iter()
+            .flat_map(|&frequency| [1, 2, 4].map(|n| (frequency * n as f32, 0.2 / n as f32)))
+            .collect();
+        let samples = mix(&partials, 16_000, 8000);
+        let analysis = analyze(&samples, 8000, &Settings { downsample: 1, ..Settings::default() }).unwrap();
+        let chord = analysis.chord(A4_HZ, Notation::default(), POLY_THRESHOLD, NOISE_FLOOR_FACTOR, POLY_MAX_PEAKS);
+        assert_eq!(chord, ["C", "E", "G"]);
+        assert_eq!(analysis.polyphony(POLY_THRESHOLD, NOISE_FLOOR_FACTOR, POLY_MAX_PEAKS), 3);
+    }
+
+    #[test]
+    fn lists_harmonics_relative_to_the_fundamental() {
+        // 200 Hz with overtones at half, a tenth and a hundredth of its amplitude, the third a
+        // little sharp
+        let samples = mix(&[(200.0, 0.4), (400.0, 0.2), (603.0, 0.04), (800.0, 0.004)], 16_000, 8000);
+        let analysis = analyze(&samples, 8000, &Settings { downsample: 1, ..Settings::default() }).unwrap();
+        let harmonics = analysis.harmonics(HARMONIC_COUNT);
+        assert_eq!(harmonics.len(), HARMONIC_COUNT);
+        let expected = [(200.0, 0.0), (400.0, -6.0), (603.0, -20.0), (800.0, -40.0)];
+        for (harmonic, (frequency, level_db)) in harmonics.iter().zip(expected) {
+            assert!((harmonic.frequency - frequency).abs() < 0.5, "{:?}", harmonic);
+            assert!((harmonic.level_db - level_db).abs() < 0.5, "{:?}", harmonic);
+        }
+        // Only the harmonics below the Nyquist frequency of 4 kHz
+        assert_eq!(analysis.harmonics(30).len(), 20);
+    }
+
+    #[test]
+    fn smoothing_keeps_noise_from_outweighing_a_faint_tone() {
+        // A faint 440 Hz tone whose pitch wanders by tens of cents, smearing it over many bins, in
+        // loud white noise
+        let mut noise = Noise::new();
+        let (mut cents, mut phase) = (0.0f32, 0.0f32);
+        let samples: Vec<f32> = (0..16_000)
+            .map(|_| {
+                cents = 0.999 * cents + 3.0 * noise.next();
+                phase += 2.0 * PI * 440.0 * 2f32.powf(cents / 1200.0) / 8000.0;
+                0.02 * phase.sin() + noise.next()
+            })
+            .collect();
+        let settings = Settings { downsample: 1, ..Settings::default() };
+        let raw = analyze(&samples, 8000, &settings).unwrap();
+        let smoothed = analyze(&samples, 8000, &Settings { smooth_spectrum: 9, ..settings }).unwrap();
+        // A noise bin outweighs every bin of the tone, but not the tone's bins together
+        assert!(raw.frequency.unwrap() > 1000.0, "{:?}", raw.frequency);
+        assert!((smoothed.frequency.unwrap() - 440.0).abs() < 10.0, "{:?}", smoothed.frequency);
+        assert_eq!(smoothed.magnitudes, raw.magnitudes);
+    }
+
+    #[test]
+    fn masks_the_spectrum_outside_the_band() {
+        // A 100 Hz bass under a louder 1 kHz tone
+        let samples = mix(&[(100.0, 0.2), (1000.0, 0.5)], 16_000, 8000);
+        let settings = Settings { downsample: 1, ..Settings::default() };
+        assert!((analyze(&samples, 8000, &settings).unwrap().frequency.unwrap() - 1000.0).abs() < 1.0);
+
+        let analysis = analyze(&samples, 8000, &Settings { band: Some(40.0..=400.0), ..settings }).unwrap();
+        assert!((analysis.frequency.unwrap() - 100.0).abs() < 1.0);
+        let chord = analysis.chord(A4_HZ, Notation::default(), POLY_THRESHOLD, NOISE_FLOOR_FACTOR, POLY_MAX_PEAKS);
+        assert_eq!(chord, ["G"]);
+        let above_band = (400.0 * analysis.fft_size as f32 / 8000.0) as usize + 1;
+        assert!(analysis.magnitudes[above_band..].iter().all(|&magnitude| magnitude == 0.0));
+    }
+
+    #[test]
+    fn downsampling_filters_out_what_would_alias() {
+        // Decimated by 8, 44.1 kHz becomes 5512.5 Hz, where 6 kHz would fold down to 487.5 Hz
+        let high = sine(6000.0, 44_100, 44_100);
+        let (decimated, rate) = downsample(&high, 44_100, 8);
+        assert_eq!((decimated.len(), rate), (5513, 5512.5));
+        // Away from the edges the filter leaves less than 1% of its level (below -40 dB)
+        assert!(rms(&decimated[100..5400]) < 0.01 * rms(&high), "{}", rms(&decimated[100..5400]));
+        let naive: Vec<f32> = high.iter().step_by(8).copied().collect();
+        assert!(rms(&naive) > 0.5 * rms(&high));
+
+        // A quiet A4 under it is still the note found, rather than the B4 the alias would make
+        let samples = mix(&[(440.0, 0.1), (6000.0, 0.5)], 44_100, 44_100);
+        let note = analyze(&samples, 44_100, &Settings::default()).unwrap().note(A4_HZ, Notation::default());
+        assert_eq!(note.unwrap().note, "A4");
+    }
+
+    #[test]
+    fn keeps_the_partial_frame_of_a_truncated_buffer() {
+        // Two stereo frames, then a left sample without its right one
+        let audio = Audio { samples: vec![0.25, 0.75, -0.5, 0.0, 0.875], channels: 2, sample_rate: 8000 };
+        assert_eq!(audio.mono(), [0.5, -0.25, 0.875]);
+        assert_eq!(audio.channel(0).unwrap(), [0.25, -0.5, 0.875]);
+        assert_eq!(audio.channel(1).unwrap(), [0.75, 0.0]);
+        assert_eq!(audio.channel(2), None);
+        // Three channels cut after the second sample of the second frame
+        assert_eq!(downmix(&[0.25, 0.5, 0.75, 0.125, 0.375], 3), [0.5, 0.25]);
+    }
+
+    #[test]
+    fn rejects_an_excerpt_shorter_than_the_minimum() {
+        let settings = Settings { downsample: 1, ..Settings::default() };
+        let tone = sine(440.0, MIN_ANALYSIS_SAMPLES, 8000);
+        let result = analyze(&tone[..MIN_ANALYSIS_SAMPLES - 1], 8000, &settings);
+        match result {
+            Err(Error::TooShort { samples, required }) => {
+                assert_eq!((samples, required), (MIN_ANALYSIS_SAMPLES - 1, MIN_ANALYSIS_SAMPLES));
+            }
+            other => panic!("expected TooShort, got {:?}", other),
+        }
+        assert!(analyze(&tone, 8000, &settings).is_ok());
+
+        // Decimated by 8, eight times as many samples are needed
+        let result = analyze(&sine(440.0, 8 * MIN_ANALYSIS_SAMPLES - 8, 8000), 8000, &Settings::default());
+        assert!(matches!(result, Err(Error::TooShort { required: MIN_ANALYSIS_SAMPLES, .. })), "{:?}", result);
+    }
+
+    #[test]
+    fn finds_no_peak_in_a_range_without_bins() {
+        // 16-point FFTs space bins 500 Hz apart, so 410-490 Hz holds none
+        let settings = Settings { fft_size: Some(16), min_freq: 410.0, max_freq: 490.0, ..Settings::default() };
+        let analysis = analyze(&sine(440.0, 8192, 8000), 8000, &Settings { downsample: 1, ..settings }).unwrap();
+        assert_eq!((analysis.max_index, analysis.frequency), (None, None));
+        assert_eq!(analysis.note(A4_HZ, Notation::default()), None);
+    }
+
+    #[test]
+    fn normalizes_the_peak_to_full_scale() {
+        let mut samples = vec![0.25, -0.5, 0.125];
+        normalize(&mut samples);
+        assert_eq!(samples, [0.5, -1.0, 0.25]);
+        let mut silence = [0.0; 4];
+        normalize(&mut silence);
+        assert_eq!(silence, [0.0; 4]);
+
+        // Scaling changes every magnitude alike, so the peak stays in its bin
+        let quiet: Vec<f32> = sine(440.0, 8192, 8000).iter().map(|s| 0.01 * s).collect();
+        let settings = Settings { downsample: 1, ..Settings::default() };
+        let plain = analyze(&quiet, 8000, &settings).unwrap();
+        let normalized = analyze(&quiet, 8000, &Settings { normalize: true, ..settings }).unwrap();
+        assert_eq!(plain.max_index, normalized.max_index);
+        assert!(normalized.magnitudes.iter().sum::<f32>() > 50.0 * plain.magnitudes.iter().sum::<f32>());
+    }
+
+    #[test]
+    fn puts_the_peak_at_zero_db() {
+        let settings = Settings { downsample: 1, ..Settings::default() };
+        let analysis = analyze(&sine(440.0, 8192, 8000), 8000, &settings).unwrap();
+        let db = magnitudes_db(&analysis.magnitudes);
+        assert_eq!(db[analysis.max_index.unwrap()], 0.0);
+        assert!(db.iter().all(|&level| level <= 0.0));
+        let mut sorted = db.clone();
+        sorted.sort_by(f32::total_cmp);
+        assert!(sorted[sorted.len() / 2] < -40.0, "noise floor {} dB", sorted[sorted.len() / 2]);
+
+        assert_eq!(magnitudes_db(&[1.0, 0.0]), [0.0, f32::NEG_INFINITY]);
+        assert!(magnitudes_db(&[0.0; 4]).iter().all(|&level| level == f32::NEG_INFINITY));
+        assert!(magnitudes_db(&[]).is_empty());
+    }
+
+    #[test]
+    fn removes_a_dc_offset_before_the_fft() {
+        // A4 riding on a DC offset larger than itself, searched from 0 Hz so bin 0 can compete
+        let samples: Vec<f32> = sine(440.0, 16_000, 8000).iter().map(|s| 0.5 + 0.3 * s).collect();
+        let settings = Settings { downsample: 1, min_freq: 0.0, ..Settings::default() };
+        let analysis = analyze(&samples, 8000, &settings).unwrap();
+        assert_ne!(analysis.max_index, Some(0));
+        assert_eq!(analysis.note(A4_HZ, Notation::default()).unwrap().note, "A4");
+
+        // Kept, the offset outweighs the tone
+        let kept = analyze(&samples, 8000, &Settings { remove_dc: false, ..settings }).unwrap();
+        assert_eq!(kept.max_index, Some(0));
+    }
+
+    #[test]
+    fn lists_the_strongest_peaks_first() {
+        let samples = mix(&[(300.0, 0.2), (500.0, 0.6), (700.0, 0.4)], 16_000, 8000);
+        let analysis = analyze(&samples, 8000, &Settings { downsample: 1, ..Settings::default() }).unwrap();
+        let peaks = analysis.top_peaks(2, A4_HZ, Notation::default());
+        assert_eq!(peaks.len(), 2);
+        assert!((peaks[0].frequency - 500.0).abs() < 0.5, "{:?}", peaks);
+        assert!((peaks[1].frequency - 700.0).abs() < 0.5, "{:?}", peaks);
+        assert_eq!((peaks[0].note.as_str(), peaks[0].midi), ("B4", 71));
+
+        // Asking for more returns the third tone next, then only the much weaker side lobes
+        let all = analysis.top_peaks(20, A4_HZ, Notation::default());
+        assert!((all[2].frequency - 300.0).abs() < 0.5, "{:?}", all);
+        assert!(all.windows(2).all(|pair| pair[0].magnitude >= pair[1].magnitude));
+        assert!(analysis.top_peaks(0, A4_HZ, Notation::default()).is_empty());
+    }
+
+    #[test]
+    fn de_emphasis_undoes_pre_emphasis() {
+        let mut noise = Noise::new();
+        let samples: Vec<f32> = (0..1000).map(|_| noise.next()).collect();
+        let emphasized = pre_emphasis(&samples, 0.97);
+        assert_ne!(emphasized, samples);
+        for (restored, original) in de_emphasis(&emphasized, 0.97).iter().zip(&samples) {
+            assert!((restored - original).abs() < 1e-5);
+        }
+        // A step passes through pre-emphasis as its edge and a small remainder
+        assert_eq!(pre_emphasis(&[1.0, 1.0, 1.0], 0.75), [1.0, 0.25, 0.25]);
+    }
+
+    #[test]
+    fn centroid_of_a_pure_tone_is_its_frequency() {
+        let settings = Settings { downsample: 1, ..Settings::default() };
+        let analysis = analyze(&sine(1000.0, 16_000, 8000), 8000, &settings).unwrap();
+        let centroid = spectral_centroid(&analysis.magnitudes, analysis.sample_rate, analysis.fft_size);
+        assert!((centroid - 1000.0).abs() < 5.0, "{} Hz", centroid);
+        assert_eq!(spectral_centroid(&[0.0; 513], 8000.0, 1024), 0.0);
+    }
+
+    #[test]
+    fn trims_silence_down_to_the_first_sound() {
+        // A tenth of a second of silence, then A4
+        let mut samples = vec![0.0; 800];
+        samples.extend(sine(440.0, 8000, 8000));
+        assert_eq!(first_sound(&samples, 8000.0, DEFAULT_SILENCE_DBFS), Some(800));
+        let settings = Settings { trim_silence: true, duration_sec: 0.5, ..Settings::default() };
+        assert_eq!(settings.excerpt(&samples, 8000.0).unwrap(), &samples[800..4800]);
+
+        // Nothing but silence has no first sound to start from
+        let silence = [0.0; 8000];
+        assert_eq!(first_sound(&silence, 8000.0, DEFAULT_SILENCE_DBFS), None);
+        assert!(matches!(settings.excerpt(&silence, 8000.0), Err(Error::NoSignal { .. })));
+        let result = analyze(&silence, 8000, &Settings { downsample: 1, ..settings });
+        let threshold = DEFAULT_SILENCE_DBFS;
+        assert!(matches!(result, Err(Error::NoSignal { threshold_dbfs }) if threshold_dbfs == threshold));
+    }
+
+    #[test]
+    fn counts_runs_at_full_scale_as_clipped() {
+        let mut samples = vec![0.5; 100];
+        samples[10] = 1.0; // A lone peak
+        samples[20..24].fill(1.0);
+        samples[50..52].fill(-1.0); // Too short a run
+        samples[70..76].fill(-0.9995);
+        assert_eq!(clipping_ratio(&samples), 0.1);
+        assert_eq!(clipping_ratio(&[]), 0.0);
+    }
+
+    #[test]
+    fn detects_a_note_from_wav_bytes() {
+        let bytes = wav(&mix(&[(440.0, 0.5)], 44_100, 44_100), 1, 44_100, 16);
+        let result = detect_note_from_bytes(&bytes).unwrap();
+        assert_eq!(result.note, "A4");
+        assert!(matches!(detect_note_from_bytes(b"RIFF"), Err(Error::Wav(_))));
+    }
+
+    #[test]
+    fn reads_24_bit_wav_like_16_bit() {
+        let tone = mix(&[(220.0, 0.8), (330.0, 0.1)], 22_050, 22_050);
+        let sixteen = read_wav_from(&wav(&tone, 1, 22_050, 16)[..]).unwrap();
+        let twenty_four = read_wav_from(&wav(&tone, 1, 22_050, 24)[..]).unwrap();
+        assert_eq!((twenty_four.channels, twenty_four.sample_rate), (1, 22_050));
+        assert_eq!(twenty_four.samples.len(), sixteen.samples.len());
+        // The same up to the 16-bit file's rounding
+        let error = sixteen.samples.iter()
+            .zip(&twenty_four.samples)
+            .map(|(a, b)| (a - b).abs())
+            .fold(0.0, f32::max);
+        assert!(error <= 0.5 / i16::MAX as f32 + 1e-6, "differ by {error}");
+        let peak = |audio: &Audio| analyze(&audio.mono(), 22_050, &Settings::default()).unwrap().max_index;
+        assert_eq!(peak(&sixteen), peak(&twenty_four));
+    }
+
+    #[test]
+    fn reads_float_wav_like_16_bit() {
+        // A sweep up through C4, E4, G4 and C5, a quarter of a second each
+        let sweep: Vec<f32> = [261.63, 329.63, 392.0, 523.25]
+            .iter()
+            .flat_map(|&frequency| mix(&[(frequency, 0.5)], 2000, 8000))
+            .collect();
+        let float = read_wav_from(&float_wav(&sweep, 1, 8000)[..]).unwrap();
+        let sixteen = read_wav_from(&wav(&sweep, 1, 8000, 16)[..]).unwrap();
+        assert_eq!(float.samples, sweep);
+        let notes = |audio: &Audio| -> Vec<i32> {
+            let analyzer = WavAnalyzer::new().downsample(1);
+            analyzer.melody(&audio.mono(), audio.sample_rate, 512, 128).iter().map(|note| note.midi).collect()
+        };
+        assert_eq!(notes(&float), [60, 64, 67, 72]);
+        assert_eq!(notes(&float), notes(&sixteen));
+    }
+
+    #[test]
+    fn survives_a_nan_sample() {
+        // Float files are read as they are, so a NaN reaches the spectrum unchanged
+        let mut samples = sine(440.0, 4096, 8000);
+        samples[100] = f32::NAN;
+        let audio = read_wav_from(&float_wav(&samples, 1, 8000)[..]).unwrap();
+        assert!(audio.samples[100].is_nan());
+        let settings = Settings { downsample: 1, ..Settings::default() };
+        for method in [Method::Fft, Method::Hps] {
+            let analysis = analyze(&audio.samples, 8000, &Settings { method, ..settings.clone() }).unwrap();
+            analysis.note(A4_HZ, Notation::default());
+            analysis.top_peaks(3, A4_HZ, Notation::default());
+        }
+        WavAnalyzer::new().downsample(1).melody(&audio.samples, 8000, 512, 128);
+    }
+
+    #[test]
+    fn reads_extensible_24_bit_wav_like_plain() {
+        // A mono 8 kHz WAV of 24-bit samples with the given format tag, container width in bytes
+        // and extension, the samples left-aligned in their containers
+        fn wav_24(samples: &[i32], format: u16, width: u16, extension: &[u8]) -> Vec<u8> {
+            let mut fmt = Vec::new();
+            fmt.extend(format.to_le_bytes());
+            fmt.extend(1u16.to_le_bytes());
+            fmt.extend(8000u32.to_le_bytes());
+            fmt.extend((8000 * width as u32).to_le_bytes());
+            fmt.extend(width.to_le_bytes());
+            fmt.extend((8 * width).to_le_bytes());
+            fmt.extend(extension);
+            let data: Vec<u8> = samples.iter()
+                .flat_map(|&s| (s << (8 * width - 24)).to_le_bytes()[..width as usize].to_vec())
+                .collect();
+            let mut bytes = b"RIFF".to_vec();
+            bytes.extend((4 + 8 + fmt.len() as u32 + 8 + data.len() as u32).to_le_bytes());
+            bytes.extend(b"WAVEfmt ");
+            bytes.extend((fmt.len() as u32).to_le_bytes());
+            bytes.extend(fmt);
+            bytes.extend(b"data");
+            bytes.extend((data.len() as u32).to_le_bytes());
+            bytes.extend(data);
+            bytes
+        }
+        // cbSize, 24 valid bits, a front-centre speaker and the PCM subformat GUID
+        let mut extension = vec![22, 0, 24, 0, 4, 0, 0, 0, 1, 0, 0, 0, 0, 0, 16, 0];
+        extension.extend([0x80, 0, 0, 0xaa, 0, 0x38, 0x9b, 0x71]);
+
+        let samples = [0, 1, -1, 0x7f_ffff, -0x80_0000, 1_234_567, -7_654_321];
+        let plain = read_wav_from(&wav_24(&samples, 1, 3, &[])[..]).unwrap();
+        assert_eq!(plain.samples[3], 0x7f_ffff as f32 / (1 << 23) as f32);
+        for width in [3, 4] {
+            let extensible = read_wav_from(&wav_24(&samples, 0xfffe, width, &extension)[..]).unwrap();
+            assert_eq!((extensible.channels, extensible.sample_rate), (1, 8000));
+            assert_eq!(extensible.samples, plain.samples, "in {} bytes", width);
+        }
+    }
+
+    #[test]
+    fn reads_unsigned_8_bit_wav() {
+        let tone = mix(&[(220.0, 0.8)], 22_050, 22_050);
+        let eight = wav(&tone, 1, 22_050, 8);
+        // Silence is stored as 128, so the first sample's byte sits at the midpoint
+        assert_eq!(eight[44], 128);
+        let audio = read_wav_from(&eight[..]).unwrap();
+        assert!((audio.samples[5] - (tone[5] * 127.0).round() / 128.0).abs() < 1e-6);
+        let note = |bytes: &[u8]| detect_note_from_bytes(bytes).unwrap().note;
+        assert_eq!(note(&eight), "A3");
+        assert_eq!(note(&eight), note(&wav(&tone, 1, 22_050, 16)));
+    }
+}
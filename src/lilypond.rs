@@ -0,0 +1,129 @@
+use crate::{quantize, NoteEvent};
+
+// LilyPond pitch names of the twelve pitch classes, sharps spelled with `is`
+const LILYPOND_NAMES: [&str; 12] = ["c", "cis", "d", "dis", "e", "f", "fis", "g", "gis", "a", "ais", "b"];
+
+// Sixty-fourth notes, the shortest written duration, per 4/4 bar, and bars per line of the music
+const UNITS_PER_BAR: usize = 64;
+const BARS_PER_LINE: usize = 4;
+
+// The written durations, longest first, as (length in sixty-fourths, LilyPond duration)
+const DURATIONS: [(usize, &str); 12] = [
+    (64, "1"),
+    (48, "2."),
+    (32, "2"),
+    (24, "4."),
+    (16, "4"),
+    (12, "8."),
+    (8, "8"),
+    (6, "16."),
+    (4, "16"),
+    (3, "32."),
+    (2, "32"),
+    (1, "64"),
+];
+
+/// Write note events as a LilyPond score titled `title`, of a single staff in 4/4 with
+/// quarter-note beats at `bpm`, which engraves the melody and renders it as MIDI.
+///
+/// Onsets and lengths come from [`quantize`] on a grid of `grid` steps per whole note (16 for
+/// sixteenths), written in durations down to a sixty-fourth, so grids that don't divide 64 land
+/// on the nearest sixty-fourth; gaps between notes become rests, and notes crossing a bar line or
+/// lasting no single written duration are tied. Overlapping notes are played one after the
+/// other. Pitches are absolute, MIDI 60 being middle C (`c'`).
+pub fn to_lilypond(notes: &[NoteEvent], bpm: f32, grid: u32, title: &str) -> String {
+    let units = |beats: f32| (beats * 16.0).round() as usize;
+
+    let mut music = Staff::default();
+    for (note, quantized) in notes.iter().zip(quantize(notes, bpm, grid.max(1))) {
+        let start = units(quantized.start_beat).max(music.position);
+        let end = start + units(quantized.beats).max(1);
+        music.push("r", start - music.position);
+        music.push(&lilypond_pitch(note.midi), end - start);
+    }
+    // Replace the bar check after the last token with the final bar line, or fill an empty
+    // score with a whole-bar rest
+    let body = match music.position {
+        0 => "r1".to_string(),
+        _ => format!("{} \\bar \"|.\"", music.text.trim_end_matches([' ', '|', '\n'])),
+    };
+    format!(
+        concat!(
+            "\\version \"2.24.0\"\n\n\\header {{\n  title = \"{}\"\n}}\n\n",
+            "\\score {{\n  {{\n    \\tempo 4 = {}\n    \\time 4/4\n    {}\n  }}\n{}",
+        ),
+        title.replace('\\', "\\\\").replace('"', "\\\""),
+        bpm.round(),
+        body.replace('\n', "\n    "),
+        "  \\layout { }\n  \\midi { }\n}\n"
+    )
+}
+
+// Absolute LilyPond name of a MIDI note number, e.g. `cis''` for 73
+fn lilypond_pitch(midi: i32) -> String {
+    let name = LILYPOND_NAMES[midi.rem_euclid(12) as usize];
+    match midi.div_euclid(12) - 1 {
+        octave if octave < 3 => format!("{}{}", name, ",".repeat((3 - octave) as usize)),
+        octave => format!("{}{}", name, "'".repeat((octave - 3) as usize)),
+    }
+}
+
+// Tracks the position in sixty-fourths while writing tokens, inserting bar checks and ties
+#[derive(Default)]
+struct Staff {
+    text: String,
+    position: usize,
+}
+
+impl Staff {
+    // Write `pitch` (or `r` for a rest) lasting `length` sixty-fourths, split at bar lines and
+    // into written durations
+    fn push(&mut self, pitch: &str, mut length: usize) {
+        while length > 0 {
+            let room = length.min(UNITS_PER_BAR - self.position % UNITS_PER_BAR);
+            let &(part, duration) =
+                DURATIONS.iter().find(|&&(part, _)| part <= room).expect("a sixty-fourth always fits");
+            self.text.push_str(pitch);
+            self.text.push_str(duration);
+            length -= part;
+            self.position += part;
+            if length > 0 && pitch != "r" {
+                self.text.push('~');
+            }
+            if self.position.is_multiple_of(UNITS_PER_BAR) {
+                let bars = self.position / UNITS_PER_BAR;
+                self.text.push_str(if bars.is_multiple_of(BARS_PER_LINE) { " |\n" } else { " | " });
+            } else {
+                self.text.push(' ');
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::note;
+
+    #[test]
+    fn names_pitches_across_octaves() {
+        let names: Vec<String> = [36, 48, 59, 60, 61, 72, 85].iter().map(|&midi| lilypond_pitch(midi)).collect();
+        assert_eq!(names, ["c,", "c", "b", "c'", "cis'", "c''", "cis'''"]);
+    }
+
+    #[test]
+    fn writes_a_melody_with_rests_and_ties() {
+        // At 120 BPM a sixteenth lasts 0.125 s
+        let notes = [note(60, 0.0, 0.5), note(62, 0.5, 0.75), note(64, 1.0, 2.625), note(67, 2.625, 2.75)];
+        let ly = to_lilypond(&notes, 120.0, 16, "The \"Lead\"");
+        assert!(ly.starts_with("\\version \"2.24.0\"\n\n\\header {\n  title = \"The \\\"Lead\\\"\"\n}\n"), "{ly}");
+        assert!(ly.contains("\\tempo 4 = 120\n    \\time 4/4\n"), "{ly}");
+        // The E lasts 13 sixteenths: 8 in the first bar, then a quarter and a sixteenth
+        assert!(ly.contains("    c'4 d'8 r8 e'2~ | e'4~ e'16 g'16 \\bar \"|.\"\n"), "{ly}");
+        assert!(ly.ends_with("  }\n  \\layout { }\n  \\midi { }\n}\n"), "{ly}");
+        // On a thirty-second grid the notes keep their lengths rather than rounding to sixteenths
+        let fine = to_lilypond(&[note(60, 0.0, 0.0625), note(62, 0.0625, 0.25)], 120.0, 32, "Fine");
+        assert!(fine.contains("    c'32 d'16. \\bar \"|.\"\n"), "{fine}");
+        assert!(to_lilypond(&[], 120.0, 16, "Silence").contains("\\time 4/4\n    r1\n  }"));
+    }
+}
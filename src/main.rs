@@ -1,113 +1,1514 @@
-use hound;
-use rustfft::FftPlanner;
-use rustfft::num_complex::Complex;
-use std::f32::consts::PI;
+use helloworld::{
+    Analysis, Audio, Emphasis, Harmonic, Instrument, Key, NoteEvent, NoteResult, PitchEstimate,
+    QuantizedNote, SampleLoop, SpectralPeak, StreamConfig, StringMatch, VelocityCurve, Vibrato,
+    WavAnalyzer, WavMetadata, Window,
+};
+use serde::Serialize;
+use rayon::prelude::*;
+use std::fmt::Display;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+const USAGE: &str = "\
+Usage: helloworld [OPTIONS] [FILE]
+       helloworld [OPTIONS] --dir DIR
+
+Detects the dominant note in a WAV, FLAC or Ogg Vorbis file (a.wav if FILE is omitted), or in every such
+file under DIR, printing one `file: note` line each. A FILE of `-` reads a complete WAV stream, header
+included, from stdin.
+
+Options:
+  --dir DIR       Detect the note of every WAV/FLAC/Ogg file under DIR (in parallel)
+  --channel N     Analyze only channel N (0-based) instead of downmixing
+  --per-channel   Only detect the note of each channel separately, e.g. L: E2 and R: A2
+  --method NAME   Pitch detector: fft, autocorr, yin, hps, cqt or cepstrum
+                  (default fft)
+  --interp NAME   Peak interpolation: parabolic or gaussian (default gaussian)
+  --start SEC     Start of the analyzed excerpt in seconds (default 0)
+  --duration SEC  Length of the analyzed excerpt in seconds (default 2)
+  --average N     Average the spectra of N half-overlapping frames of the excerpt before
+                  picking the peak, for a steadier reading of a sustained note (default 1,
+                  a single FFT)
+  --downsample N  Decimation factor before analysis, at least 1 (default 8)
+  --fft-size N    FFT length of the note's analysis, at least 2: the excerpt is zero-padded or cut
+                  to N samples, for the same bin spacing in every file (default the next power of
+                  two above its length)
+  --target-rate HZ
+                  Resample to this rate before analysis instead of decimating by --downsample,
+                  e.g. 22050 for a 48 kHz file
+  --min-freq HZ   Lowest frequency to search, for the note and in every frame (default 20)
+  --max-freq HZ   Highest frequency to search, for the note and in every frame (default 4000)
+  --band LOW:HIGH Set the spectrum outside LOW to HIGH Hz to zero before picking any peak, e.g.
+                  40:400 for the bass: unlike --min-freq and --max-freq it also hides the rest
+                  from --top, --poly and --harmonics
+  --keep-dc       Don't subtract the excerpt's mean (DC offset) before the FFT
+  --preemph A     Apply pre-emphasis y[n] = x[n] - A*x[n-1] before the FFT, boosting highs
+                  (0.97 is typical)
+  --deemph A      Apply de-emphasis y[n] = x[n] + A*y[n-1] before the FFT, boosting lows
+  --trim-silence  Count --start from the first sound instead of the start of the file
+  --silence-threshold DB
+                  Level in dBFS that counts as sound for --trim-silence (default -50)
+  --fix-octave    Move the FFT peak down an octave when half its frequency is also strong, for
+                  tones whose second harmonic is louder than the fundamental
+  --multires      Read the spectrum above 1 kHz from frames 32 times shorter than the excerpt's,
+                  which keep wavering overtones sharp while the long frame resolves the bass
+  --smooth-spectrum N
+                  Find the peak on a moving average of the spectrum over N bins, steadier for a
+                  noisy tone, then refine it on the raw spectrum (default 1, no smoothing)
+  --precise       Refine the frequency from the phase advance between two overlapping frames,
+                  reading a steady test tone to within 0.1 Hz, for calibration
+  --normalize     Scale the analyzed excerpt to a peak of 1.0 before the FFT
+  --clip-threshold FRACTION
+                  Warn when more than this fraction of the samples is clipped (default 0.001)
+  --reject-clipped
+                  Fail on a clipped recording instead of warning and analyzing it anyway
+  --tuning HZ     Reference frequency of A4 (default 440)
+  --notation NAME Note names: english-sharp, english-flat, german (H for B, B for Bb) or
+                  solfege (default english-sharp)
+  --transpose N   Move every reported note N semitones, e.g. 2 to read for a B-flat instrument:
+                  the names and MIDI numbers of the notes and every format change, the measured
+                  frequencies and cents don't (default 0)
+  --octave-offset N
+                  Add N to the octave number of the note names in the text output, e.g. 1 for
+                  software that calls middle C C5; MIDI numbers and the JSON, CSV and notation
+                  formats are unchanged (default 0)
+  --window NAME   FFT window: hann, hamming, blackman, blackman-harris, flat-top (for accurate
+                  levels), gaussian or rectangular (default hann)
+  --window-sigma S
+                  Width of --window gaussian as a fraction of half the frame, smaller for finer
+                  time and coarser frequency resolution (default 0.4)
+  --out FILE      Write the detected note to a MIDI file
+  --synth FILE    Write a sine at the detected pitch to a WAV file for checking by ear; a
+                  recording that splits into several notes is rendered note by note, and one
+                  with no pitch fails
+  --velocity NAME How --out maps loudness to velocity: linear or log (default log)
+  --frame N       STFT frame length in samples (default 2048)
+  --hop N         STFT hop between frames in samples (default 512)
+  --stream        Only list the notes, reading the WAV file in blocks so memory use stays flat
+                  however long it is (ignores --channel, --smooth and --target-rate)
+  --smooth N      Median-filter the pitch track over N frames before splitting it into notes
+                  (default 1, no smoothing)
+  --min-note-ms MS
+                  Drop notes shorter than MS milliseconds, which are mostly spurious; real notes
+                  that short, such as quick staccato, are lost too (default 0, keep every note)
+  --merge-short   Absorb notes shorter than --min-note-ms into a neighbour of the same pitch
+                  instead of dropping them, rejoining notes they split (not with --stream)
+  --tuner         Only estimate the note with a few Goertzel filters, without a full FFT
+  --live          Listen to the default input device and show the note like --tuner every 100 ms,
+                  on a pitch meter in a terminal (q to quit) or overwriting one line otherwise
+                  (needs the live feature)
+  --instrument NAME
+                  Also report the nearest open string in standard tuning and which way to tune
+                  it: guitar (E2 A2 D3 G3 B3 E4) or bass (E1 A1 D2 G2)
+  --top N         List the N strongest spectral peaks with their notes
+  --harmonics     List the frequency and level of the fundamental and its first 7 overtones
+  --poly          Also list the pitch classes of every strong spectral peak (chord) and count the
+                  distinct notes among them, not counting overtones (polyphony)
+  --chroma        Print the pitch-class profile (chromagram) of the whole recording
+  --vibrato       Measure the vibrato rate and depth of each note
+  --onsets        List the onset times found in the spectral flux, whatever their pitch
+  --tempo         Estimate the tempo in BPM from the onsets, which stands in for --bpm when
+                  that isn't given
+  --key           Estimate the musical key of the whole recording, e.g. G major
+  --db            Log the magnitudes of -vv in dB relative to the strongest bin
+  --format NAME   Output on stdout: text, json, csv (the per-frame pitch track), midi (the
+                  notes as a MIDI file), abc (the notes as ABC notation), musicxml (the notes
+                  as a MusicXML score) or lilypond (the notes as a LilyPond score) (default text)
+  --bpm BPM       Tempo in quarter notes per minute: snaps each note to the --grid, reporting
+                  its onset and length in beats, and sets the tempo of --format abc, musicxml
+                  and lilypond (default 120)
+  --grid N        Steps per whole note of the --bpm grid, e.g. 8 for eighth notes (default 16)
+  --json          Same as --format json
+  --csv FILE      Write the per-frame pitch track to a CSV file
+  --spectrum-out FILE
+                  Write the magnitude of every FFT bin up to --max-freq to a CSV file, in dB
+                  with --db
+  -v, -vv         Also log diagnostics on stderr: sample rates and FFT sizes, and with -vv the
+                  first FFT magnitudes; RUST_LOG refines this further";
+
+// Command-line options
+struct Options {
+    path: String,
+    dir: Option<String>,
+    channel: Option<usize>,
+    per_channel: bool,
+    analyzer: WavAnalyzer,
+    octave_offset: i32,
+    clip_threshold: f32,
+    reject_clipped: bool,
+    midi_out: Option<String>,
+    synth_out: Option<String>,
+    velocity: VelocityCurve,
+    frame: usize,
+    hop: usize,
+    smooth: usize,
+    min_note_ms: f32,
+    merge_short: bool,
+    stream: bool,
+    tuner: bool,
+    live: bool,
+    instrument: Option<Instrument>,
+    top: usize,
+    harmonics: bool,
+    poly: bool,
+    chroma: bool,
+    key: bool,
+    tempo: bool,
+    onsets: bool,
+    vibrato: bool,
+    db: bool,
+    verbosity: u8,
+    format: Format,
+    bpm: Option<f32>,
+    grid: Option<u32>,
+    csv_out: Option<String>,
+    spectrum_out: Option<String>,
+}
+
+// Parse a flag's value, failing with a message naming the flag
+fn parse_value<T: FromStr>(flag: &str, value: Option<String>) -> Result<T, String>
+where
+    T::Err: Display,
+{
+    let value = value.ok_or_else(|| format!("{} needs a value", flag))?;
+    value.parse().map_err(|err| format!("invalid value '{}' for {}: {}", value, flag, err))
+}
+
+// Parse the command line described by USAGE
+fn parse_args() -> Result<Options, String> {
+    let mut options = Options {
+        path: "a.wav".to_string(),
+        dir: None,
+        channel: None,
+        per_channel: false,
+        analyzer: WavAnalyzer::new(),
+        octave_offset: 0,
+        clip_threshold: helloworld::DEFAULT_CLIP_THRESHOLD,
+        reject_clipped: false,
+        midi_out: None,
+        synth_out: None,
+        velocity: VelocityCurve::default(),
+        frame: helloworld::DEFAULT_FRAME,
+        hop: helloworld::DEFAULT_HOP,
+        smooth: 1,
+        min_note_ms: 0.0,
+        merge_short: false,
+        stream: false,
+        tuner: false,
+        live: false,
+        instrument: None,
+        top: 0,
+        harmonics: false,
+        poly: false,
+        chroma: false,
+        key: false,
+        tempo: false,
+        onsets: false,
+        vibrato: false,
+        db: false,
+        verbosity: 0,
+        format: Format::default(),
+        bpm: None,
+        grid: None,
+        csv_out: None,
+        spectrum_out: None,
+    };
+
+    // Applied once all options are read, so it doesn't matter whether it comes before --window
+    let mut window_sigma = None;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--dir" => options.dir = Some(parse_value(&arg, args.next())?),
+            "--channel" => options.channel = Some(parse_value(&arg, args.next())?),
+            "--per-channel" => options.per_channel = true,
+            "--method" => options.analyzer = options.analyzer.method(parse_value(&arg, args.next())?),
+            "--window" => options.analyzer = options.analyzer.window(parse_value(&arg, args.next())?),
+            "--window-sigma" => {
+                let sigma: f32 = parse_value(&arg, args.next())?;
+                if !(sigma > 0.0 && sigma.is_finite()) {
+                    return Err("--window-sigma must be a finite number above 0".to_string());
+                }
+                window_sigma = Some(sigma);
+            }
+            "--interp" => options.analyzer = options.analyzer.interpolation(parse_value(&arg, args.next())?),
+            "--average" => {
+                let frames = parse_value(&arg, args.next())?;
+                if frames == 0 {
+                    return Err("--average must be at least 1 frame".to_string());
+                }
+                options.analyzer = options.analyzer.average_frames(frames);
+            }
+            "--fft-size" => {
+                let size = parse_value(&arg, args.next())?;
+                if size < 2 {
+                    return Err("--fft-size must be at least 2".to_string());
+                }
+                options.analyzer = options.analyzer.fft_size(size);
+            }
+            "--target-rate" => {
+                let rate = parse_value(&arg, args.next())?;
+                if rate == 0 {
+                    return Err("--target-rate must be a positive rate in Hz".to_string());
+                }
+                options.analyzer = options.analyzer.target_rate(rate);
+            }
+            "--downsample" => {
+                let factor = parse_value(&arg, args.next())?;
+                if factor == 0 {
+                    return Err("--downsample must be at least 1".to_string());
+                }
+                options.analyzer = options.analyzer.downsample(factor);
+            }
+            "--min-freq" => options.analyzer = options.analyzer.min_freq(parse_value(&arg, args.next())?),
+            "--max-freq" => options.analyzer = options.analyzer.max_freq(parse_value(&arg, args.next())?),
+            "--start" => {
+                let seconds = parse_value(&arg, args.next())?;
+                if seconds < 0.0 {
+                    return Err("--start can't be negative".to_string());
+                }
+                options.analyzer = options.analyzer.start(seconds);
+            }
+            "--duration" => {
+                let seconds = parse_value(&arg, args.next())?;
+                if seconds <= 0.0 {
+                    return Err("--duration must be positive".to_string());
+                }
+                options.analyzer = options.analyzer.duration(seconds);
+            }
+            "--clip-threshold" => {
+                options.clip_threshold = parse_value(&arg, args.next())?;
+                if !(0.0..=1.0).contains(&options.clip_threshold) {
+                    return Err("--clip-threshold must be a fraction from 0 to 1".to_string());
+                }
+            }
+            "--reject-clipped" => options.reject_clipped = true,
+            "--tuning" => {
+                let a4_hz = parse_value(&arg, args.next())?;
+                if a4_hz <= 0.0 {
+                    return Err("--tuning must be a positive frequency in Hz".to_string());
+                }
+                options.analyzer = options.analyzer.tuning(a4_hz);
+            }
+            "--out" => options.midi_out = Some(parse_value(&arg, args.next())?),
+            "--synth" => options.synth_out = Some(parse_value(&arg, args.next())?),
+            "--velocity" => options.velocity = parse_value(&arg, args.next())?,
+            "--frame" => {
+                options.frame = parse_value(&arg, args.next())?;
+                if options.frame < 2 {
+                    return Err("--frame must be at least 2 samples".to_string());
+                }
+            }
+            "--hop" => {
+                options.hop = parse_value(&arg, args.next())?;
+                if options.hop == 0 {
+                    return Err("--hop must be at least 1 sample".to_string());
+                }
+            }
+            "--min-note-ms" => {
+                options.min_note_ms = parse_value(&arg, args.next())?;
+                if options.min_note_ms < 0.0 {
+                    return Err("--min-note-ms can't be negative".to_string());
+                }
+            }
+            "--merge-short" => options.merge_short = true,
+            "--precise" => options.analyzer = options.analyzer.precise(true),
+            "--smooth-spectrum" => {
+                let width = parse_value(&arg, args.next())?;
+                if width == 0 {
+                    return Err("--smooth-spectrum must be at least 1 bin".to_string());
+                }
+                options.analyzer = options.analyzer.smooth_spectrum(width);
+            }
+            "--smooth" => {
+                options.smooth = parse_value(&arg, args.next())?;
+                if options.smooth == 0 {
+                    return Err("--smooth must be at least 1 frame".to_string());
+                }
+            }
+            "--stream" => options.stream = true,
+            "--band" => {
+                let Band(band) = parse_value(&arg, args.next())?;
+                options.analyzer = options.analyzer.band(band);
+            }
+            "--keep-dc" => options.analyzer = options.analyzer.remove_dc(false),
+            "--preemph" | "--deemph" => {
+                let coeff: f32 = parse_value(&arg, args.next())?;
+                if !(0.0..1.0).contains(&coeff) {
+                    return Err(format!("{} must be at least 0 and below 1", arg));
+                }
+                let emphasis = if arg == "--preemph" { Emphasis::Pre(coeff) } else { Emphasis::De(coeff) };
+                options.analyzer = options.analyzer.emphasis(Some(emphasis));
+            }
+            "--trim-silence" => options.analyzer = options.analyzer.trim_silence(true),
+            "--silence-threshold" => {
+                options.analyzer = options.analyzer.silence_threshold(parse_value(&arg, args.next())?)
+            }
+            "--fix-octave" => options.analyzer = options.analyzer.fix_octave(true),
+            "--multires" => options.analyzer = options.analyzer.multires(true),
+            "--normalize" => options.analyzer = options.analyzer.normalize(true),
+            "--notation" => options.analyzer = options.analyzer.notation(parse_value(&arg, args.next())?),
+            "--octave-offset" => options.octave_offset = parse_value(&arg, args.next())?,
+            "--transpose" => options.analyzer = options.analyzer.transpose(parse_value(&arg, args.next())?),
+            "--tuner" => options.tuner = true,
+            "--live" => options.live = true,
+            "--instrument" => options.instrument = Some(parse_value(&arg, args.next())?),
+            "--top" => options.top = parse_value(&arg, args.next())?,
+            "--harmonics" => options.harmonics = true,
+            "--poly" => options.poly = true,
+            "--chroma" => options.chroma = true,
+            "--key" => options.key = true,
+            "--tempo" => options.tempo = true,
+            "--onsets" => options.onsets = true,
+            "--vibrato" => options.vibrato = true,
+            "--db" => options.db = true,
+            "--format" => options.format = parse_value(&arg, args.next())?,
+            "--json" => options.format = Format::Json,
+            "--bpm" => {
+                let bpm = parse_value(&arg, args.next())?;
+                if bpm <= 0.0 {
+                    return Err("--bpm must be a positive tempo".to_string());
+                }
+                options.bpm = Some(bpm);
+            }
+            "--grid" => {
+                let grid = parse_value(&arg, args.next())?;
+                if grid == 0 {
+                    return Err("--grid must be at least 1 step per whole note".to_string());
+                }
+                options.grid = Some(grid);
+            }
+            "--csv" => options.csv_out = Some(parse_value(&arg, args.next())?),
+            "--spectrum-out" => options.spectrum_out = Some(parse_value(&arg, args.next())?),
+            "-v" | "--verbose" => options.verbosity += 1,
+            "-vv" => options.verbosity += 2,
+            "--help" | "-h" => {
+                println!("{}", USAGE);
+                std::process::exit(0);
+            }
+            _ if arg.starts_with("--") => return Err(format!("unknown option '{}'", arg)),
+            _ => options.path = arg,
+        }
+    }
+    if let Some(sigma) = window_sigma {
+        if !matches!(options.analyzer.settings.window, Window::Gaussian(_)) {
+            return Err("--window-sigma only applies to --window gaussian".to_string());
+        }
+        options.analyzer = options.analyzer.window(Window::Gaussian(sigma));
+    }
+    let settings = &options.analyzer.settings;
+    if settings.min_freq < 0.0 || settings.min_freq >= settings.max_freq {
+        return Err("--min-freq must be at least 0 and below --max-freq".to_string());
+    }
+    Ok(options)
+}
+
+/// What was found in a recording, printed in the chosen --format; in this schema for json. Field names
+/// are part of the output format; add fields rather than renaming them. The library types in it
+/// serialize as objects with their fields under the same names.
+#[derive(Serialize)]
+struct Report<'a> {
+    /// Title of the recording: the name in a WAV file's LIST/INFO chunk, or else the file name
+    /// without its extension. Also names the MIDI track and the notation formats' scores.
+    title: String,
+    /// Cue points of a WAV file's cue chunk, in sample frames. Only present when it has any.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    cues: Vec<u32>,
+    /// Sustain loops (start, end) of a WAV file's smpl chunk, in sample frames. Only present when
+    /// it has any.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    loops: Vec<SampleLoop>,
+    /// Detected note (frequency, note, midi, cents, confidence, rms, dbfs), or null if no pitch
+    /// was found in range.
+    result: Option<&'a NoteResult>,
+    /// Open string closest to the detected note (name, midi, cents), positive cents meaning tune
+    /// down. Only present with --instrument and a detected note.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    string: Option<StringMatch>,
+    /// Notes segmented from the spectrogram (midi, start_sec, end_sec, rms), in order of onset.
+    notes: &'a [NoteEvent],
+    /// Magnitude-weighted mean frequency of the analyzed excerpt's spectrum in Hz.
+    spectral_centroid: f32,
+    /// The --top strongest spectral peaks (frequency, magnitude, note, midi), strongest first. Only
+    /// present with --top.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    peaks: Vec<SpectralPeak>,
+    /// The fundamental and its overtones (number, frequency, level_db), levels in dB relative to
+    /// the fundamental. Only present with --harmonics and a detected note.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    harmonics: Vec<Harmonic>,
+    /// Chromagram of the whole recording, C first, peaking at 1.0. Only present with --chroma.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    chroma: Option<[f32; 12]>,
+    /// Estimated key, e.g. "G major". Only present with --key.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    key: Option<String>,
+    /// Estimated tempo in beats per minute, or 0.0 when none was found. Only present with --tempo.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tempo_bpm: Option<f32>,
+    /// Onset times in seconds, in order. Only present with --onsets.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    onsets: Option<Vec<f32>>,
+    /// Vibrato (rate_hz, depth_cents) of each of the notes, in the same order, or null for notes
+    /// without a clear vibrato. Only present with --vibrato.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    vibrato: Vec<Option<Vibrato>>,
+    /// Onset and length in beats (start_beat, beats) of each of the notes, in the same order,
+    /// snapped to the --grid. Only present with --bpm or --grid.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    quantized: Vec<QuantizedNote>,
+}
 
 fn main() {
-    // Open the WAV file
-    let mut reader = hound::WavReader::open("a.wav").expect("Failed to open file");
-
-    // Get the WAV file specifications
-    let spec = reader.spec();
-    let sample_rate = spec.sample_rate;
-    println!("Sample rate: {}", sample_rate);
-
-    // Collect samples based on the bit depth or format, and convert stereo to mono
-    let samples: Vec<f32> = match spec.sample_format {
-        hound::SampleFormat::Int => {
-            match spec.bits_per_sample {
-                16 => reader.samples::<i16>()
-                    .enumerate()
-                    .map(|(i, s)| (s.unwrap() as f32) / std::i16::MAX as f32)
-                    .collect::<Vec<f32>>(),
-                _ => panic!("Unsupported bit depth for integer samples!"),
+    let options = match parse_args() {
+        Ok(options) => options,
+        Err(err) => {
+            eprintln!("Error: {}\n\n{}", err, USAGE);
+            std::process::exit(2);
+        }
+    };
+    init_logging(options.verbosity);
+    if let Some(dir) = &options.dir {
+        run_batch(Path::new(dir), &options);
+        return;
+    }
+    if options.stream {
+        run_stream(&options);
+        return;
+    }
+    if options.live {
+        run_live(&options);
+        return;
+    }
+    if options.per_channel {
+        run_per_channel(&options);
+        return;
+    }
+    let path = &options.path;
+
+    let (samples, sample_rate) = load_samples(Path::new(path), options.channel).unwrap_or_else(|err| {
+        eprintln!("Error: {}: {}", path, err);
+        std::process::exit(1);
+    });
+    let clipped = helloworld::clipping_ratio(&samples);
+    if clipped > options.clip_threshold {
+        if options.reject_clipped {
+            eprintln!("Error: {}: {:.2}% of the samples are clipped", path, clipped * 100.0);
+            std::process::exit(1);
+        }
+        log::warn!(
+            "{}: {:.2}% of the samples are clipped; its harmonics may be mistaken for notes",
+            path,
+            clipped * 100.0
+        );
+    }
+    if options.tuner {
+        print_tuner(&samples, sample_rate, &options);
+        return;
+    }
+    let analysis = match options.analyzer.analyze(&samples, sample_rate) {
+        Ok(analysis) => analysis,
+        Err(err) => {
+            eprintln!("Error: {}: {}", path, err);
+            std::process::exit(1);
+        }
+    };
+    if let Some(spectrum_out) = &options.spectrum_out {
+        if let Err(err) = write_spectrum(Path::new(spectrum_out), &analysis, options.db) {
+            eprintln!("Error: could not write '{}': {}", spectrum_out, err);
+            std::process::exit(1);
+        }
+    }
+    let result = analysis.note(options.analyzer.tuning, options.analyzer.notation)
+        .map(|result| result.transposed(options.analyzer.transpose, options.analyzer.notation));
+
+    // Short-time spectrum of the whole recording, and the melody from the pitch of each frame
+    let (downsampled_samples, downsampled_rate) = options.analyzer.settings.convert_rate(&samples, sample_rate);
+    let spectrogram = options.analyzer.stft(&downsampled_samples, options.frame, options.hop);
+    log_diagnostics(&options, sample_rate, &analysis, &spectrogram);
+    let track = options.analyzer.pitch_track(&spectrogram, downsampled_rate);
+    let pitches: Vec<Option<f32>> = track.iter().map(|estimate| estimate.map(|e| e.frequency)).collect();
+    let pitches = helloworld::median_filter(&pitches, options.smooth);
+    let levels = helloworld::frame_rms(&downsampled_samples, options.frame, options.hop);
+    let notes =
+        helloworld::segment_notes(&pitches, &levels, options.hop, downsampled_rate, options.analyzer.tuning);
+    let notes = if options.min_note_ms > 0.0 {
+        helloworld::drop_short_notes(&notes, options.min_note_ms / 1000.0, options.merge_short)
+    } else {
+        notes
+    };
+    // The notes as written after --transpose, for every output; --synth still plays the ones heard
+    let written: Vec<NoteEvent> = notes.iter().map(|note| note.transposed(options.analyzer.transpose)).collect();
+    let profile = (options.chroma || options.key)
+        .then(|| chroma_profile(&spectrogram, downsampled_rate, options.frame, options.analyzer.tuning));
+    let chroma = profile.filter(|_| options.chroma);
+    let key = profile.filter(|_| options.key).map(|profile| helloworld::estimate_key(&profile));
+
+    let vibrato = if options.vibrato {
+        notes.iter().map(|note| helloworld::note_vibrato(&downsampled_samples, downsampled_rate, note)).collect()
+    } else {
+        Vec::new()
+    };
+
+    let flux = (options.tempo || options.onsets).then(|| {
+        let frames = options.analyzer.stft(&downsampled_samples, helloworld::TEMPO_FRAME, helloworld::TEMPO_HOP);
+        helloworld::spectral_flux(&frames)
+    });
+    let tempo_bpm = flux.as_ref()
+        .filter(|_| options.tempo)
+        .map(|flux| helloworld::estimate_bpm(flux, helloworld::TEMPO_HOP, downsampled_rate));
+    let onsets = flux.as_ref()
+        .filter(|_| options.onsets)
+        .map(|flux| helloworld::detect_onsets(flux, helloworld::TEMPO_HOP, downsampled_rate));
+
+    // Notes snapped to the tempo grid, which the notation and MIDI formats write in place of the raw ones
+    let bpm = options.bpm.or(tempo_bpm.filter(|&bpm| bpm > 0.0)).unwrap_or(helloworld::DEFAULT_BPM);
+    let grid = options.grid.unwrap_or(helloworld::DEFAULT_GRID);
+    let quantized = if options.bpm.is_some() || options.grid.is_some() {
+        helloworld::quantize(&written, bpm, grid)
+    } else {
+        Vec::new()
+    };
+    let snapped: Vec<NoteEvent> = if quantized.is_empty() {
+        written.clone()
+    } else {
+        quantized.iter().zip(&written).map(|(q, note)| q.snap(note, bpm)).collect()
+    };
+
+    let metadata = source_metadata(Path::new(path));
+    let report = Report {
+        title: source_title(Path::new(path), &metadata),
+        cues: metadata.cues,
+        loops: metadata.loops,
+        result: result.as_ref(),
+        string: options.instrument.zip(result.as_ref())
+            .map(|(instrument, result)| instrument.nearest_string(result.frequency, options.analyzer.tuning)),
+        notes: &written,
+        spectral_centroid: helloworld::spectral_centroid(
+            &analysis.magnitudes,
+            analysis.sample_rate,
+            analysis.fft_size,
+        ),
+        peaks: analysis.top_peaks(options.top, options.analyzer.tuning, options.analyzer.notation)
+            .into_iter()
+            .map(|peak| {
+                let midi = peak.midi + options.analyzer.transpose;
+                SpectralPeak { note: options.analyzer.notation.note_name(midi), midi, ..peak }
+            })
+            .collect(),
+        harmonics: if options.harmonics && result.is_some() {
+            analysis.harmonics(helloworld::HARMONIC_COUNT)
+        } else {
+            Vec::new()
+        },
+        chroma,
+        key: key.map(|key| {
+            let tonic = (key.tonic as i32 + options.analyzer.transpose).rem_euclid(12) as usize;
+            Key { tonic, ..key }.name(options.analyzer.notation)
+        }),
+        tempo_bpm,
+        onsets,
+        vibrato,
+        quantized,
+    };
+    let csv = CsvOutput {
+        track: &track,
+        hop: options.hop,
+        sample_rate: downsampled_rate,
+        analyzer: &options.analyzer,
+    };
+    let output: Box<dyn OutputWriter> = match options.format {
+        Format::Text => {
+            Box::new(TextOutput { options: &options, analysis: &analysis })
+        }
+        Format::Json => Box::new(JsonOutput),
+        Format::Csv => Box::new(csv),
+        Format::Midi => Box::new(MidiOutput { velocity: options.velocity, notes: &snapped }),
+        Format::Abc => Box::new(AbcOutput { bpm, notes: &snapped }),
+        Format::MusicXml => Box::new(MusicXmlOutput { bpm, grid, notes: &snapped }),
+        Format::LilyPond => Box::new(LilyPondOutput { bpm, grid, notes: &snapped }),
+    };
+    let mut stdout = io::stdout().lock();
+    if let Err(err) = output.write(&report, &mut stdout).and_then(|()| stdout.flush()) {
+        eprintln!("Error: could not write output: {}", err);
+        std::process::exit(1);
+    }
+
+    if let Some(csv_out) = &options.csv_out {
+        let written = File::create(csv_out).and_then(|file| {
+            let mut out = BufWriter::new(file);
+            csv.write(&report, &mut out)?;
+            out.flush()
+        });
+        if let Err(err) = written {
+            eprintln!("Error: could not write '{}': {}", csv_out, err);
+            std::process::exit(1);
+        }
+    }
+
+    if let (Some(midi_out), Some(result)) = (&options.midi_out, &result) {
+        let note = NoteEvent {
+            midi: result.midi,
+            start_sec: 0.0,
+            end_sec: analysis.duration(),
+            rms: result.rms,
+        };
+        if let Err(err) = helloworld::write_midi(&[note], options.velocity, &report.title, Path::new(midi_out)) {
+            eprintln!("Error: could not write '{}': {}", midi_out, err);
+            std::process::exit(1);
+        }
+    }
+
+    if let Some(synth_out) = &options.synth_out {
+        // Several notes are rendered in sequence; otherwise the detected pitch fills the excerpt,
+        // and without one there's nothing to render
+        let samples = match &result {
+            _ if notes.len() > 1 => helloworld::render_notes(&notes, options.analyzer.tuning, sample_rate),
+            Some(result) => helloworld::sine(result.frequency, analysis.duration(), result.rms, sample_rate),
+            None => {
+                eprintln!("Error: could not write '{}': no pitch detected to render", synth_out);
+                std::process::exit(1);
+            }
+        };
+        if let Err(err) = helloworld::write_wav(&samples, sample_rate, Path::new(synth_out)) {
+            eprintln!("Error: could not write '{}': {}", synth_out, err);
+            std::process::exit(1);
+        }
+    }
+}
+
+// Log warnings, and with -v or -vv debug or trace messages too, as bare lines on stderr
+fn init_logging(verbosity: u8) {
+    let level = match verbosity {
+        0 => log::LevelFilter::Warn,
+        1 => log::LevelFilter::Debug,
+        _ => log::LevelFilter::Trace,
+    };
+    env_logger::Builder::new()
+        .filter_level(level)
+        .parse_default_env()
+        .format(|out, record| match record.level() {
+            log::Level::Error => writeln!(out, "Error: {}", record.args()),
+            log::Level::Warn => writeln!(out, "Warning: {}", record.args()),
+            _ => writeln!(out, "{}", record.args()),
+        })
+        .init();
+}
+
+// Log the intermediate values of the analysis that help with debugging it
+fn log_diagnostics(options: &Options, sample_rate: u32, analysis: &Analysis, spectrogram: &[Vec<f32>]) {
+    log::debug!("Sample rate: {}", sample_rate);
+    log::debug!("Analysis sample rate: {}", analysis.sample_rate);
+    log::debug!("FFT size: {} ({} samples zero-padded)", analysis.fft_size, analysis.window_size);
+
+    // The first few magnitudes
+    if options.db {
+        let levels = helloworld::magnitudes_db(&analysis.magnitudes);
+        for (i, level) in levels.iter().take(10).enumerate() {
+            log::trace!("Magnitude at index {}: {:.1} dB", i, level);
+        }
+        if let Some(index) = analysis.max_index {
+            log::trace!("Max index: {} ({:.1} dB)", index, levels[index]);
+        }
+    } else {
+        for (i, &magnitude) in analysis.magnitudes.iter().take(10).enumerate() {
+            log::trace!("Magnitude at index {}: {:.5}", i, magnitude);
+        }
+        if let Some(index) = analysis.max_index {
+            log::trace!("Max index: {}", index);
+        }
+    }
+
+    log::debug!(
+        "Spectrogram: {} frames of {} bins ({:.1} ms hop)",
+        spectrogram.len(),
+        options.frame / 2 + 1,
+        options.hop as f32 * 1000.0 / analysis.sample_rate
+    );
+    if let Some(frequency) = analysis.frequency {
+        log::debug!("Dominant frequency (before filtering): {:.2} Hz", frequency);
+    }
+}
+
+// Chromagram summed over every frame of a spectrogram, scaled so the strongest class is 1.0
+fn chroma_profile(spectrogram: &[Vec<f32>], sample_rate: f32, frame: usize, a4_hz: f32) -> [f32; 12] {
+    let mut chroma = [0.0; 12];
+    for magnitudes in spectrogram {
+        let classes = helloworld::chromagram(magnitudes, sample_rate, frame, a4_hz);
+        for (total, class) in chroma.iter_mut().zip(classes) {
+            *total += class;
+        }
+    }
+    helloworld::normalize(&mut chroma);
+    chroma
+}
+
+// Print the note found by the Goertzel tuner in the analyzed excerpt
+fn print_tuner(samples: &[f32], sample_rate: u32, options: &Options) {
+    let settings = &options.analyzer.settings;
+    let (downsampled_samples, downsampled_rate) = settings.convert_rate(samples, sample_rate);
+    let excerpt = settings.excerpt(&downsampled_samples, downsampled_rate).unwrap_or_else(|err| {
+        eprintln!("Error: {}: {}", options.path, err);
+        std::process::exit(1);
+    });
+    match helloworld::tuner_pitch(excerpt, downsampled_rate, options.analyzer.tuning) {
+        Some(frequency) => {
+            println!("Tuner: {}", tuner_reading(frequency, options));
+            if let Some(instrument) = options.instrument {
+                println!("{}", string_line(&instrument.nearest_string(frequency, options.analyzer.tuning)));
+            }
+        }
+        None => println!("No pitch detected"),
+    }
+}
+
+// Name, MIDI number and cents deviation of the note nearest a tuner's frequency
+fn tuner_note(frequency: f32, options: &Options) -> (String, i32, f32) {
+    let (_, cents) = helloworld::frequency_to_note_cents(frequency, options.analyzer.tuning);
+    let midi = helloworld::frequency_to_midi(frequency, options.analyzer.tuning) + options.analyzer.transpose;
+    (note_name(midi, options), midi, cents)
+}
+
+// The note nearest a tuner's frequency, e.g. "A4 (69) -0.2 cents at 439.96 Hz"
+fn tuner_reading(frequency: f32, options: &Options) -> String {
+    let (note, midi, cents) = tuner_note(frequency, options);
+    format!("{} ({}) {:+.1} cents at {:.2} Hz", note, midi, cents, frequency)
+}
+
+// Tune in real time: capture the default input device at its own rate and channel count, and
+// every 100 ms run the Goertzel tuner on the latest 100 ms, mixed down to mono. On a terminal the
+// reading is drawn as a pitch meter until q, Esc or Ctrl-C; otherwise each reading overwrites the
+// previous line.
+#[cfg(feature = "live")]
+fn run_live(options: &Options) {
+    use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+    use std::io::IsTerminal;
+    use std::sync::mpsc;
+
+    let fail = |err: &dyn Display| -> ! {
+        eprintln!("Error: live input: {}", err);
+        std::process::exit(1);
+    };
+    let device = cpal::default_host().default_input_device().unwrap_or_else(|| fail(&"no input device"));
+    let supported = device.default_input_config().unwrap_or_else(|err| fail(&err));
+    let channels = supported.channels() as usize;
+    let sample_rate = supported.sample_rate();
+
+    // The callback runs on the audio thread, so it only mixes down and hands the block over
+    let (sender, receiver) = mpsc::channel::<Vec<f32>>();
+    let on_error = |err: cpal::Error| log::warn!("live input: {}", err);
+    let config = supported.config();
+    let stream = match supported.sample_format() {
+        cpal::SampleFormat::F32 => build_live_stream::<f32>(&device, config, channels, sender, on_error),
+        cpal::SampleFormat::I16 => build_live_stream::<i16>(&device, config, channels, sender, on_error),
+        cpal::SampleFormat::U16 => build_live_stream::<u16>(&device, config, channels, sender, on_error),
+        cpal::SampleFormat::I32 => build_live_stream::<i32>(&device, config, channels, sender, on_error),
+        cpal::SampleFormat::I8 => build_live_stream::<i8>(&device, config, channels, sender, on_error),
+        cpal::SampleFormat::U8 => build_live_stream::<u8>(&device, config, channels, sender, on_error),
+        format => fail(&format!("unsupported sample format {}", format)),
+    }
+    .unwrap_or_else(|err| fail(&err));
+    stream.play().unwrap_or_else(|err| fail(&err));
+    log::debug!("Listening at {} Hz with {} channel(s)", sample_rate, channels);
+
+    let meter = io::stdout().is_terminal();
+    if meter {
+        meter::enter().unwrap_or_else(|err| fail(&err));
+    }
+    let block = (sample_rate as usize / 10).max(1);
+    let settings = &options.analyzer.settings;
+    let mut buffer = Vec::new();
+    loop {
+        // Wake up now and then even without input, so a key press is noticed promptly
+        match receiver.recv_timeout(std::time::Duration::from_millis(50)) {
+            Ok(samples) => buffer.extend(samples),
+            Err(mpsc::RecvTimeoutError::Timeout) => {}
+            Err(mpsc::RecvTimeoutError::Disconnected) => break,
+        }
+        if meter && meter::quit_requested().unwrap_or(true) {
+            break;
+        }
+        let Some(latest) = take_latest(&mut buffer, block) else {
+            continue;
+        };
+        let (downsampled, downsampled_rate) = settings.convert_rate(&latest, sample_rate);
+        let pitch = if helloworld::to_dbfs(helloworld::rms(&latest)) > settings.silence_dbfs {
+            helloworld::tuner_pitch(&downsampled, downsampled_rate, options.analyzer.tuning)
+        } else {
+            None
+        };
+        let shown = if meter { meter::draw(pitch, options) } else { print_live_line(pitch, options) };
+        if let Err(err) = shown {
+            if meter {
+                let _ = meter::leave();
+            }
+            fail(&err);
+        }
+    }
+    if meter {
+        meter::leave().unwrap_or_else(|err| fail(&err));
+    }
+}
+
+// The newest `block` samples once that many have arrived, dropping any older ones so the readings
+// keep up with the input rather than falling behind it
+#[cfg(any(feature = "live", test))]
+fn take_latest(buffer: &mut Vec<f32>, block: usize) -> Option<Vec<f32>> {
+    if buffer.len() < block {
+        return None;
+    }
+    let latest = buffer.split_off(buffer.len() - block);
+    buffer.clear();
+    Some(latest)
+}
+
+// Deviation within which the meter's needle turns green
+#[cfg(any(feature = "live", test))]
+const IN_TUNE_CENTS: f32 = 5.0;
+
+// Cells of the meter's scale on either side of its center, each 2 cents wide, spanning ±50 cents
+#[cfg(any(feature = "live", test))]
+const HALF_WIDTH: usize = 25;
+#[cfg(any(feature = "live", test))]
+const CENTS_PER_CELL: f32 = 2.0;
+
+// The cell of the meter's scale, from 0 to 2 * HALF_WIDTH, the needle points at for a deviation
+// in cents, and whether that counts as in tune; deviations past the ends pin it to the last cell
+#[cfg(any(feature = "live", test))]
+fn needle_cell(cents: f32) -> (usize, bool) {
+    let offset = (cents / CENTS_PER_CELL).round().clamp(-(HALF_WIDTH as f32), HALF_WIDTH as f32);
+    ((HALF_WIDTH as f32 + offset) as usize, cents.abs() <= IN_TUNE_CENTS)
+}
+
+// Overwrite the current line with a live reading
+#[cfg(feature = "live")]
+fn print_live_line(pitch: Option<f32>, options: &Options) -> io::Result<()> {
+    let line = match pitch {
+        Some(frequency) => match options.instrument {
+            Some(instrument) => {
+                let string = instrument.nearest_string(frequency, options.analyzer.tuning);
+                format!("{}; {}", tuner_reading(frequency, options), string_line(&string))
+            }
+            None => tuner_reading(frequency, options),
+        },
+        None => "No pitch detected".to_string(),
+    };
+    // Return to the start of the line and clear it before writing the new reading
+    let mut out = io::stdout().lock();
+    write!(out, "\r\x1b[K{}", line)?;
+    out.flush()
+}
+
+// Full-screen pitch meter for --live: the note and frequency over a needle that shows the
+// deviation in cents, green while the note is in tune
+#[cfg(feature = "live")]
+mod meter {
+    use super::{needle_cell, string_line, tuner_note, Options, HALF_WIDTH};
+    use crossterm::cursor::{Hide, MoveTo, Show};
+    use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyModifiers};
+    use crossterm::style::{Print, PrintStyledContent, Stylize};
+    use crossterm::terminal::{self, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen};
+    use crossterm::{execute, queue};
+    use std::io::{self, Write};
+    use std::time::Duration;
+
+    // Take over the terminal: raw mode for single key presses, on its own screen, without a cursor
+    pub(super) fn enter() -> io::Result<()> {
+        terminal::enable_raw_mode()?;
+        execute!(io::stdout(), EnterAlternateScreen, Hide)
+    }
+
+    // Give the terminal back as it was
+    pub(super) fn leave() -> io::Result<()> {
+        execute!(io::stdout(), Show, LeaveAlternateScreen)?;
+        terminal::disable_raw_mode()
+    }
+
+    // Whether q, Esc or Ctrl-C was pressed since the last call; raw mode turns Ctrl-C into a key
+    pub(super) fn quit_requested() -> io::Result<bool> {
+        while event::poll(Duration::ZERO)? {
+            if let Event::Key(KeyEvent { code, modifiers, .. }) = event::read()? {
+                match code {
+                    KeyCode::Char('q') | KeyCode::Esc => return Ok(true),
+                    KeyCode::Char('c') if modifiers.contains(KeyModifiers::CONTROL) => return Ok(true),
+                    _ => {}
+                }
             }
+        }
+        Ok(false)
+    }
+
+    // Redraw the meter for the latest reading
+    pub(super) fn draw(pitch: Option<f32>, options: &Options) -> io::Result<()> {
+        let mut out = io::stdout().lock();
+        queue!(out, Clear(ClearType::All), MoveTo(2, 1))?;
+        let Some(frequency) = pitch else {
+            queue!(out, Print("No pitch detected"), MoveTo(2, 8), Print("Press q to quit"))?;
+            return out.flush();
+        };
+
+        let (note, _, cents) = tuner_note(frequency, options);
+        let (needle, in_tune) = needle_cell(cents);
+        let reading = format!("{:<4} {:8.2} Hz {:+6.1} cents", note, frequency, cents);
+        queue!(out, PrintStyledContent(reading.bold()))?;
+
+        // The scale with the needle on it
+        queue!(out, MoveTo(2, 3))?;
+        for cell in 0..=2 * HALF_WIDTH {
+            match cell {
+                _ if cell == needle && in_tune => queue!(out, PrintStyledContent("█".green()))?,
+                _ if cell == needle => queue!(out, PrintStyledContent("█".red()))?,
+                _ if cell == HALF_WIDTH => queue!(out, Print("┼"))?,
+                _ => queue!(out, Print("─"))?,
+            }
+        }
+        queue!(out, MoveTo(2, 4), Print(format!("{:<23}{:^5}{:>23}", "-50", "0", "+50")))?;
+
+        if let Some(instrument) = options.instrument {
+            let string = instrument.nearest_string(frequency, options.analyzer.tuning);
+            queue!(out, MoveTo(2, 6), Print(string_line(&string)))?;
+        }
+        queue!(out, MoveTo(2, 8), Print("Press q to quit"))?;
+        out.flush()
+    }
+}
+
+// An input stream of `T` samples that sends each block, mixed down to mono, to `sender`
+#[cfg(feature = "live")]
+fn build_live_stream<T>(
+    device: &cpal::Device,
+    config: cpal::StreamConfig,
+    channels: usize,
+    sender: std::sync::mpsc::Sender<Vec<f32>>,
+    on_error: impl FnMut(cpal::Error) + Send + 'static,
+) -> Result<cpal::Stream, cpal::Error>
+where
+    T: cpal::SizedSample,
+    f32: cpal::FromSample<T>,
+{
+    use cpal::traits::DeviceTrait;
+
+    device.build_input_stream(
+        config,
+        move |data: &[T], _: &cpal::InputCallbackInfo| {
+            let samples: Vec<f32> = data.iter().map(|&sample| sample.to_sample::<f32>()).collect();
+            // The receiver only goes away when the program exits
+            let _ = sender.send(helloworld::downmix(&samples, channels));
         },
-        _ => panic!("Unsupported format!"),
+        on_error,
+        None,
+    )
+}
+
+#[cfg(not(feature = "live"))]
+fn run_live(_options: &Options) {
+    eprintln!("Error: live input isn't available in this build; rebuild with --features live");
+    std::process::exit(1);
+}
+
+// The nearest open string and which way to tune it, e.g. "Nearest string: A string, +8.0 cents (tune down)"
+fn string_line(string: &StringMatch) -> String {
+    format!("Nearest string: {} string, {:+.1} cents ({})", string.name, string.cents, string.direction())
+}
+
+// Load a WAV, FLAC or Ogg Vorbis file, or a WAV file from stdin for "-"
+fn load_audio(path: &Path) -> Result<Audio, String> {
+    let audio = if path == Path::new("-") {
+        helloworld::read_wav_from(io::stdin().lock())
+    } else {
+        helloworld::read_audio(path)
     };
+    audio.map_err(|err| err.to_string())
+}
 
-    // Combine stereo channels to mono by averaging left and right channels
-    let mono_samples: Vec<f32> = samples.chunks(2).map(|chunk| (chunk[0] + chunk[1]) / 2.0).collect();
+// Load a WAV, FLAC or Ogg Vorbis file as the requested channel, or all channels mixed down to mono
+fn load_samples(path: &Path, channel: Option<usize>) -> Result<(Vec<f32>, u32), String> {
+    let audio = load_audio(path)?;
+    let samples = match channel {
+        None => audio.mono(),
+        Some(channel) => audio.channel(channel).ok_or_else(|| {
+            format!("channel {} is out of range (the file has {} channels)", channel, audio.channels)
+        })?,
+    };
+    Ok((samples, audio.sample_rate))
+}
 
-    // Further downsample the signal (e.g., by a factor of 10)
-    let downsample_factor = 8;
-    let downsampled_samples: Vec<f32> = mono_samples.into_iter().step_by(downsample_factor).collect();
-    let downsampled_sample_rate = sample_rate / downsample_factor as u32;
-    println!("Downsampled sample rate: {}", downsampled_sample_rate);
+// Title, cue points and loops of a WAV file; stdin, other formats and unreadable metadata have none
+fn source_metadata(path: &Path) -> WavMetadata {
+    if path == Path::new("-") {
+        return WavMetadata::default();
+    }
+    helloworld::read_wav_metadata(path).unwrap_or_else(|err| {
+        log::debug!("{}: no metadata: {}", path.display(), err);
+        WavMetadata::default()
+    })
+}
 
-    // Use only the first few seconds of audio (e.g., 2 seconds)
-    let max_samples = (downsampled_sample_rate * 2) as usize;  // First 2 seconds of audio
-    let limited_samples: Vec<f32> = downsampled_samples.into_iter().take(max_samples).collect();
+// The title in a file's metadata, or else its name without the extension
+fn source_title(path: &Path, metadata: &WavMetadata) -> String {
+    match (&metadata.title, path.file_stem()) {
+        (Some(title), _) => title.clone(),
+        (None, Some(stem)) if path != Path::new("-") => stem.to_string_lossy().into_owned(),
+        (None, _) => "stdin".to_string(),
+    }
+}
 
-    // Apply Hann window to reduce spectral leakage
-    let windowed_samples: Vec<f32> = limited_samples.iter()
-        .enumerate()
-        .map(|(n, &sample)| sample * hann_window(n, limited_samples.len())) // Apply the window function
-        .collect();
+// Every .wav, .flac and .ogg file under `dir`, recursively, in sorted order
+fn find_wav_files(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            files.extend(find_wav_files(&path)?);
+        } else if path.extension()
+            .is_some_and(|ext| ["wav", "flac", "ogg"].iter().any(|known| ext.eq_ignore_ascii_case(known)))
+        {
+            files.push(path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
 
-    // Use the actual sample size as the FFT size
-    let fft_size = windowed_samples.len();
-    println!("FFT size: {}", fft_size);
-    
-    let mut planner = FftPlanner::new();
-    let fft = planner.plan_fft_forward(fft_size);
+// Detect the note of every WAV, FLAC and Ogg Vorbis file in a directory, in parallel, printing one line per file.
+// Files that fail are reported on stderr and skipped.
+fn run_batch(dir: &Path, options: &Options) {
+    let files = find_wav_files(dir).unwrap_or_else(|err| {
+        eprintln!("Error: {}: {}", dir.display(), err);
+        std::process::exit(1);
+    });
 
-    // Convert samples into complex numbers (with imaginary part = 0)
-    let mut buffer: Vec<Complex<f32>> = windowed_samples.iter()
-        .map(|&sample| Complex { re: sample, im: 0.0 })
+    let results: Vec<Result<Option<NoteResult>, String>> = files.par_iter()
+        .map(|path| {
+            let (samples, sample_rate) = load_samples(path, options.channel)?;
+            options.analyzer.detect(&samples, sample_rate).map_err(|err| err.to_string())
+        })
         .collect();
 
-    // Apply the FFT
-    fft.process(&mut buffer);
+    for (path, result) in files.iter().zip(results) {
+        // Label a file with the title in its metadata, if it has one, after its name
+        let name = path.strip_prefix(dir).unwrap_or(path).display().to_string();
+        let name = match source_metadata(path).title {
+            Some(title) => format!("{} \"{}\"", name, title),
+            None => name,
+        };
+        match result {
+            Ok(Some(result)) => println!(
+                "{}: {} ({}) {:+.1} cents, confidence {:.2}",
+                name, note_name(result.midi, options), result.midi, result.cents, result.confidence
+            ),
+            Ok(None) => println!("{}: no pitch detected", name),
+            Err(err) => eprintln!("Error: {}: {}", name, err),
+        }
+    }
+}
+
+// Detect the note of each channel of a file on its own, printing one line per channel labeled L and R
+// for stereo, Mono for mono, and by index otherwise
+fn run_per_channel(options: &Options) {
+    let path = &options.path;
+    let audio = load_audio(Path::new(path)).unwrap_or_else(|err| {
+        eprintln!("Error: {}: {}", path, err);
+        std::process::exit(1);
+    });
+    for index in 0..audio.channels {
+        let label = match (audio.channels, index) {
+            (1, _) => "Mono".to_string(),
+            (2, 0) => "L".to_string(),
+            (2, _) => "R".to_string(),
+            (_, index) => index.to_string(),
+        };
+        let samples = audio.channel(index).expect("channel index is below the channel count");
+        match options.analyzer.detect(&samples, audio.sample_rate) {
+            Ok(Some(result)) => println!(
+                "{}: {} ({}) {:+.1} cents, confidence {:.2}",
+                label, note_name(result.midi, options), result.midi, result.cents, result.confidence
+            ),
+            Ok(None) => println!("{}: no pitch detected", label),
+            Err(err) => {
+                eprintln!("Error: {}: {}", path, err);
+                std::process::exit(1);
+            }
+        }
+    }
+}
 
-    // Calculate the magnitudes of the FFT result
-    let magnitudes: Vec<f32> = buffer.iter().map(|c| c.norm()).collect();
+// Print the notes of a WAV file or stdin as they're detected, without loading it whole
+fn run_stream(options: &Options) {
+    let path = &options.path;
+    let input: Box<dyn Read> = if path == "-" {
+        Box::new(io::stdin().lock())
+    } else {
+        match File::open(path) {
+            Ok(file) => Box::new(BufReader::new(file)),
+            Err(err) => {
+                eprintln!("Error: {}: {}", path, helloworld::Error::FileOpen(err));
+                std::process::exit(1);
+            }
+        }
+    };
+    let config = StreamConfig {
+        downsample: options.analyzer.settings.downsample,
+        frame: options.frame,
+        hop: options.hop,
+        tuning: options.analyzer.tuning,
+        settings: options.analyzer.settings.clone(),
+    };
+    let notes = helloworld::detect_notes_streaming(input, config).unwrap_or_else(|err| {
+        eprintln!("Error: {}: {}", path, err);
+        std::process::exit(1);
+    });
+    println!("Notes:");
+    for note in notes {
+        match note {
+            // Notes arrive one at a time, so short ones can only be dropped, not merged
+            Ok(note) if note.duration() < options.min_note_ms / 1000.0 => {}
+            Ok(note) => {
+                println!("{}", note_line(&note.transposed(options.analyzer.transpose), options))
+            }
+            Err(err) => {
+                eprintln!("Error: {}: {}", path, err);
+                std::process::exit(1);
+            }
+        }
+    }
+}
 
-    // Print the first few magnitudes for debugging
-    for (i, &magnitude) in magnitudes.iter().take(10).enumerate() {
-        println!("Magnitude at index {}: {:.5}", i, magnitude);
+// Write one CSV row per FFT bin from 0 Hz up to the highest searched frequency
+fn write_spectrum(path: &Path, analysis: &Analysis, db: bool) -> io::Result<()> {
+    let levels = if db { helloworld::magnitudes_db(&analysis.magnitudes) } else { analysis.magnitudes.clone() };
+    let mut out = BufWriter::new(File::create(path)?);
+    writeln!(out, "{}", if db { "frequency_hz,magnitude_db" } else { "frequency_hz,magnitude" })?;
+    let last = *analysis.search_bins().end();
+    for (bin, level) in levels.iter().enumerate().take(last + 1) {
+        writeln!(out, "{:.2},{:.6}", analysis.bin_frequency(bin as f32), level)?;
     }
+    out.flush()
+}
 
-    // Find the index of the maximum magnitude (dominant frequency), but limit search to lower frequencies
-    let search_range = fft_size / 4;  // Limit search to the first 1/4 of the FFT bins (to focus on lower frequencies)
-    let max_index = magnitudes.iter()
-        .take(search_range)  // Only search in the lower frequency range
-        .enumerate()
-        .max_by(|a, b| a.1.partial_cmp(b.1).unwrap())
-        .map(|(index, _)| index)
-        .unwrap_or(0);
-    println!("Max index: {}", max_index);
+// Format of the report written to stdout (--format)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+enum Format {
+    #[default]
+    Text,
+    Json,
+    Csv,
+    Midi,
+    Abc,
+    MusicXml,
+    LilyPond,
+}
 
-    // Calculate the dominant frequency in Hz
-    let dominant_frequency = max_index as f32 * downsampled_sample_rate as f32 / fft_size as f32;
-    println!("Dominant frequency (before filtering): {:.2} Hz", dominant_frequency);
+impl FromStr for Format {
+    type Err = String;
 
-    // Filter out frequencies outside of the range 20 Hz to 4,000 Hz
-    if dominant_frequency < 20.0 || dominant_frequency > 4000.0 {
-        println!("Dominant frequency out of expected range: {:.2} Hz", dominant_frequency);
-    } else {
-        let note_name = frequency_to_note_name(dominant_frequency);
-        println!("Dominant frequency: {:.2} Hz", dominant_frequency);
-        println!("Closest musical note: {}", note_name);
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(Format::Text),
+            "json" => Ok(Format::Json),
+            "csv" => Ok(Format::Csv),
+            "midi" => Ok(Format::Midi),
+            "abc" => Ok(Format::Abc),
+            "musicxml" => Ok(Format::MusicXml),
+            "lilypond" => Ok(Format::LilyPond),
+            _ => Err("expected text, json, csv, midi, abc, musicxml or lilypond".to_string()),
+        }
+    }
+}
+
+// Writes a report in one output format. Implementations carry whatever else their format needs
+// and write to any stream, so the same one serves stdout and --csv's file.
+trait OutputWriter {
+    fn write(&self, report: &Report, out: &mut dyn Write) -> io::Result<()>;
+}
+
+// Human-readable output
+struct TextOutput<'a> {
+    options: &'a Options,
+    analysis: &'a Analysis,
+}
+
+impl OutputWriter for TextOutput<'_> {
+    fn write(&self, report: &Report, out: &mut dyn Write) -> io::Result<()> {
+        let TextOutput { options, analysis } = *self;
+        let notation = options.analyzer.notation;
+        writeln!(out, "Title: {}", report.title)?;
+        if !report.loops.is_empty() {
+            let loops: Vec<String> = report.loops.iter()
+                .map(|sample_loop| format!("{}-{}", sample_loop.start, sample_loop.end))
+                .collect();
+            writeln!(out, "Loops: {} (frames)", loops.join(", "))?;
+        }
+        if !report.cues.is_empty() {
+            let cues: Vec<String> = report.cues.iter().map(|cue| cue.to_string()).collect();
+            writeln!(out, "Cues: {} (frames)", cues.join(", "))?;
+        }
+        writeln!(out, "Spectral centroid: {:.1} Hz", report.spectral_centroid)?;
+        writeln!(out, "Notes:")?;
+        for (i, note) in report.notes.iter().enumerate() {
+            let mut line = note_line(note, options);
+            if let Some(quantized) = report.quantized.get(i) {
+                line += &format!(", beat {:.2} for {:.2} beats", quantized.start_beat, quantized.beats);
+            }
+            match report.vibrato.get(i) {
+                Some(Some(vibrato)) => {
+                    line += &format!(", vibrato {:.1} Hz, ±{:.0} cents", vibrato.rate_hz, vibrato.depth_cents)
+                }
+                Some(None) => line += ", no vibrato",
+                None => {}
+            }
+            writeln!(out, "{}", line)?;
+        }
+
+        if let Some(chroma) = &report.chroma {
+            let classes: Vec<String> = (0..12)
+                .map(|class| format!("{} {:.2}", notation.pitch_class(class), chroma[class as usize]))
+                .collect();
+            writeln!(out, "Chroma: {}", classes.join(", "))?;
+        }
+
+        if let Some(key) = &report.key {
+            writeln!(out, "Key: {}", key)?;
+        }
+
+        match report.tempo_bpm {
+            Some(bpm) if bpm > 0.0 => writeln!(out, "Tempo: {:.1} BPM", bpm)?,
+            Some(_) => writeln!(out, "Tempo: unknown")?,
+            None => {}
+        }
+
+        if let Some(onsets) = &report.onsets {
+            if onsets.is_empty() {
+                writeln!(out, "Onsets: none")?;
+            } else {
+                let times: Vec<String> = onsets.iter().map(|time| format!("{:.2}", time)).collect();
+                writeln!(out, "Onsets: {} s", times.join(", "))?;
+            }
+        }
+
+        if !report.peaks.is_empty() {
+            writeln!(out, "Strongest peaks:")?;
+            for (rank, peak) in report.peaks.iter().enumerate() {
+                writeln!(
+                    out,
+                    "  {}. {:>8.2} Hz  {:<4} ({:>3})  magnitude {:.3}",
+                    rank + 1,
+                    peak.frequency,
+                    note_name(peak.midi, options),
+                    peak.midi,
+                    peak.magnitude
+                )?;
+            }
+        }
+
+        if options.poly {
+            // Naming the peaks against an A4 moved down by --transpose names each one that much higher
+            let chord = analysis.chord(
+                options.analyzer.tuning * 2f32.powf(-options.analyzer.transpose as f32 / 12.0),
+                notation,
+                helloworld::POLY_THRESHOLD,
+                helloworld::NOISE_FLOOR_FACTOR,
+                helloworld::POLY_MAX_PEAKS,
+            );
+            writeln!(out, "Chord notes: {}", chord.join(" "))?;
+            let polyphony = analysis.polyphony(
+                helloworld::POLY_THRESHOLD,
+                helloworld::NOISE_FLOOR_FACTOR,
+                helloworld::POLY_MAX_PEAKS,
+            );
+            writeln!(out, "Polyphony: {}", polyphony)?;
+        }
+
+        let Some(frequency) = analysis.frequency else {
+            return writeln!(out, "No pitch detected");
+        };
+
+        match report.result {
+            Some(result) => {
+                writeln!(out, "Dominant frequency: {:.2} Hz", result.frequency)?;
+                let note = note_name(result.midi, options);
+                writeln!(out, "Closest musical note: {} ({}) {:+.1} cents", note, result.midi, result.cents)?;
+                if let Some(string) = &report.string {
+                    writeln!(out, "{}", string_line(string))?;
+                }
+                writeln!(out, "Confidence: {:.2}", result.confidence)?;
+                writeln!(out, "Loudness: RMS {:.3} ({:.1} dBFS)", result.rms, result.dbfs)?;
+                if !report.harmonics.is_empty() {
+                    writeln!(out, "Harmonics:")?;
+                    for harmonic in &report.harmonics {
+                        let Harmonic { number, frequency, level_db } = harmonic;
+                        writeln!(out, "  {number}. {frequency:>8.2} Hz  {level_db:>6.1} dB")?;
+                    }
+                }
+                Ok(())
+            }
+            None => writeln!(out, "Dominant frequency out of expected range: {:.2} Hz", frequency),
+        }
     }
 }
 
-// Hann window function to reduce spectral leakage
-fn hann_window(n: usize, size: usize) -> f32 {
-    0.5 * (1.0 - (2.0 * PI * n as f32 / (size as f32 - 1.0)).cos())
+// The report itself, pretty-printed
+struct JsonOutput;
+
+impl OutputWriter for JsonOutput {
+    fn write(&self, report: &Report, out: &mut dyn Write) -> io::Result<()> {
+        serde_json::to_writer_pretty(&mut *out, report)?;
+        writeln!(out)
+    }
+}
+
+// One CSV row per analysis frame of the pitch track; silent frames leave every field but the
+// time empty
+#[derive(Clone, Copy)]
+struct CsvOutput<'a> {
+    track: &'a [Option<PitchEstimate>],
+    hop: usize,
+    sample_rate: f32,
+    // Tunes and names the notes, with --transpose applied to their MIDI numbers first but not
+    // --octave-offset, which is for reading the text output
+    analyzer: &'a WavAnalyzer,
+}
+
+impl OutputWriter for CsvOutput<'_> {
+    fn write(&self, _report: &Report, out: &mut dyn Write) -> io::Result<()> {
+        writeln!(out, "time_sec,frequency_hz,midi,note,confidence")?;
+        for (frame, estimate) in self.track.iter().enumerate() {
+            let time = (frame * self.hop) as f32 / self.sample_rate;
+            match estimate {
+                Some(estimate) => {
+                    let midi = helloworld::frequency_to_midi(estimate.frequency, self.analyzer.tuning);
+                    let midi = midi + self.analyzer.transpose;
+                    writeln!(
+                        out,
+                        "{:.4},{:.2},{},{},{:.3}",
+                        time,
+                        estimate.frequency,
+                        midi,
+                        self.analyzer.notation.note_name(midi),
+                        estimate.confidence
+                    )?
+                }
+                None => writeln!(out, "{:.4},,,,", time)?,
+            }
+        }
+        Ok(())
+    }
 }
 
-// Convert frequency to musical note name
-fn frequency_to_note_name(frequency: f32) -> String {
-    let note_number = 12.0 * (frequency / 440.0).log2() + 69.0; // Use MIDI note number for A4 = 69
-    let rounded_note_number = note_number.round() as i32;
+// Frequency band of --band, written LOW:HIGH in Hz
+struct Band(RangeInclusive<f32>);
+
+impl FromStr for Band {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let band = s.split_once(':').and_then(|(low, high)| Some(low.parse::<f32>().ok()?..=high.parse().ok()?));
+        match band {
+            Some(band) if *band.start() >= 0.0 && band.start() < band.end() => Ok(Band(band)),
+            _ => Err("expected LOW:HIGH in Hz, LOW at least 0 and below HIGH".to_string()),
+        }
+    }
+}
+
+// The segmented notes, quantized with --bpm or --grid, as a Standard MIDI File
+struct MidiOutput<'a> {
+    velocity: VelocityCurve,
+    notes: &'a [NoteEvent],
+}
 
-    // A list of note names starting from C
-    let note_names = ["C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B"];
-    
-    // Find the corresponding note and octave
-    let note_index = rounded_note_number % 12;
-    let octave = (rounded_note_number / 12) - 1; // Octave adjustment for MIDI standard
-    
-    format!("{}{}", note_names[note_index as usize], octave)
+impl OutputWriter for MidiOutput<'_> {
+    fn write(&self, report: &Report, out: &mut dyn Write) -> io::Result<()> {
+        helloworld::write_midi_to(self.notes, self.velocity, &report.title, out)
+    }
+}
+
+// The segmented notes, quantized with --bpm or --grid, as an ABC tune
+struct AbcOutput<'a> {
+    bpm: f32,
+    notes: &'a [NoteEvent],
+}
+
+impl OutputWriter for AbcOutput<'_> {
+    fn write(&self, report: &Report, out: &mut dyn Write) -> io::Result<()> {
+        out.write_all(helloworld::to_abc(self.notes, self.bpm, &report.title).as_bytes())
+    }
+}
+
+// The segmented notes, quantized with --bpm or --grid, as a MusicXML score on the --grid
+struct MusicXmlOutput<'a> {
+    bpm: f32,
+    grid: u32,
+    notes: &'a [NoteEvent],
+}
+
+impl OutputWriter for MusicXmlOutput<'_> {
+    fn write(&self, report: &Report, out: &mut dyn Write) -> io::Result<()> {
+        out.write_all(helloworld::to_musicxml(self.notes, self.bpm, self.grid, &report.title).as_bytes())
+    }
+}
+
+// The segmented notes, quantized with --bpm or --grid, as a LilyPond score on the --grid
+struct LilyPondOutput<'a> {
+    bpm: f32,
+    grid: u32,
+    notes: &'a [NoteEvent],
+}
+
+impl OutputWriter for LilyPondOutput<'_> {
+    fn write(&self, report: &Report, out: &mut dyn Write) -> io::Result<()> {
+        out.write_all(helloworld::to_lilypond(self.notes, self.bpm, self.grid, &report.title).as_bytes())
+    }
+}
+
+// Name of a MIDI note number in the text output: in the chosen notation, with --octave-offset
+// added to its octave number
+fn note_name(midi: i32, options: &Options) -> String {
+    options.analyzer.notation.note_name_with_offset(midi, options.octave_offset)
+}
+
+// One indented line of the note list
+fn note_line(note: &NoteEvent, options: &Options) -> String {
+    format!(
+        "  {:<4} ({:>3}) at {:>6.2} s for {:.2} s, {:>6.1} dBFS",
+        note_name(note.midi, options),
+        note.midi,
+        note.start_sec,
+        note.duration(),
+        helloworld::to_dbfs(note.rms)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn needle_turns_green_within_five_cents() {
+        assert_eq!(needle_cell(0.0), (HALF_WIDTH, true));
+        assert_eq!(needle_cell(5.0), (HALF_WIDTH + 3, true));
+        assert_eq!(needle_cell(-5.0), (HALF_WIDTH - 3, true));
+        assert_eq!(needle_cell(5.1), (HALF_WIDTH + 3, false));
+        assert_eq!(needle_cell(-12.0), (HALF_WIDTH - 6, false));
+    }
+
+    #[test]
+    fn needle_stops_at_the_ends_of_the_scale() {
+        assert_eq!(needle_cell(50.0), (2 * HALF_WIDTH, false));
+        assert_eq!(needle_cell(-50.0), (0, false));
+        assert_eq!(needle_cell(80.0), (2 * HALF_WIDTH, false));
+        assert_eq!(needle_cell(-700.0), (0, false));
+    }
+
+    #[test]
+    fn keeps_only_the_newest_block() {
+        let mut buffer = vec![1.0, 2.0];
+        assert_eq!(take_latest(&mut buffer, 3), None);
+        assert_eq!(buffer, [1.0, 2.0]);
+        buffer.extend([3.0, 4.0, 5.0]);
+        assert_eq!(take_latest(&mut buffer, 3), Some(vec![3.0, 4.0, 5.0]));
+        assert!(buffer.is_empty());
+    }
 }
@@ -0,0 +1,167 @@
+use crate::Error;
+use serde::Serialize;
+use std::fs::File;
+use std::io::{self, BufReader, Read};
+use std::path::Path;
+
+/// Metadata a WAV file carries in chunks besides its samples, as found by [`read_wav_metadata`].
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct WavMetadata {
+    /// Name from the `INAM` entry of the `LIST`/`INFO` chunk, if any.
+    pub title: Option<String>,
+    /// Positions of the `cue ` chunk's cue points in sample frames, in the file's order.
+    pub cues: Vec<u32>,
+    /// Loops of the `smpl` (sampler) chunk, in the file's order.
+    pub loops: Vec<SampleLoop>,
+}
+
+/// A sustain loop of a sampler WAV, in sample frames from the start of the file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct SampleLoop {
+    /// First frame of the loop.
+    pub start: u32,
+    /// Last frame of the loop, which is played too.
+    pub end: u32,
+}
+
+/// Read the title, cue points and loops of a WAV file (see [`read_wav_metadata_from`]).
+pub fn read_wav_metadata(path: &Path) -> Result<WavMetadata, Error> {
+    let file = File::open(path).map_err(Error::FileOpen)?;
+    read_wav_metadata_from(BufReader::new(file))
+}
+
+/// Read the title, cue points and loops of a WAV stream, skipping over its samples.
+///
+/// A stream that isn't a RIFF WAV file, or lacks those chunks, has no metadata rather than being
+/// an error; so has a chunk cut short by the end of the stream, while the ones before it are
+/// kept. Only failing to read the stream is an error.
+pub fn read_wav_metadata_from<R: Read>(mut reader: R) -> Result<WavMetadata, Error> {
+    let mut metadata = WavMetadata::default();
+    let mut header = [0; 12];
+    if !read_full(&mut reader, &mut header)? || &header[0..4] != b"RIFF" || &header[8..12] != b"WAVE" {
+        return Ok(metadata);
+    }
+
+    let mut chunk_header = [0; 8];
+    while read_full(&mut reader, &mut chunk_header)? {
+        let size = u32_at(&chunk_header, 4) as u64;
+        // Chunks are padded to an even length
+        let padded = size + size % 2;
+        match &chunk_header[0..4] {
+            b"LIST" | b"cue " | b"smpl" => {
+                let mut body = Vec::new();
+                if reader.by_ref().take(padded).read_to_end(&mut body).map_err(Error::FileOpen)? < size as usize {
+                    break;
+                }
+                match &chunk_header[0..4] {
+                    b"LIST" => metadata.title = metadata.title.or(info_title(&body)),
+                    b"cue " => metadata.cues.extend(cue_points(&body)),
+                    _ => metadata.loops.extend(sample_loops(&body)),
+                }
+            }
+            _ => {
+                let skipped = io::copy(&mut reader.by_ref().take(padded), &mut io::sink());
+                if skipped.map_err(Error::FileOpen)? < size {
+                    break;
+                }
+            }
+        }
+    }
+    Ok(metadata)
+}
+
+// Fill `buffer` from `reader`, returning false if the stream ends first
+fn read_full<R: Read>(reader: &mut R, buffer: &mut [u8]) -> Result<bool, Error> {
+    match reader.read_exact(buffer) {
+        Ok(()) => Ok(true),
+        Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => Ok(false),
+        Err(err) => Err(Error::FileOpen(err)),
+    }
+}
+
+// Little-endian u32 at `offset` of `bytes`
+fn u32_at(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(bytes[offset..offset + 4].try_into().expect("four bytes"))
+}
+
+// The INAM entry of a LIST chunk of type INFO: a string padded with NULs
+fn info_title(body: &[u8]) -> Option<String> {
+    let mut entries = body.strip_prefix(b"INFO")?;
+    while entries.len() >= 8 {
+        let size = (u32_at(entries, 4) as usize).min(entries.len() - 8);
+        let value = &entries[8..8 + size];
+        if &entries[0..4] == b"INAM" {
+            let title = String::from_utf8_lossy(value).trim_end_matches('\0').trim().to_string();
+            return (!title.is_empty()).then_some(title);
+        }
+        entries = &entries[(8 + size + size % 2).min(entries.len())..];
+    }
+    None
+}
+
+// Sample offsets of a cue chunk's points: a count, then 24 bytes per point with the offset last
+fn cue_points(body: &[u8]) -> Vec<u32> {
+    let count = if body.len() >= 4 { u32_at(body, 0) as usize } else { 0 };
+    body.get(4..).unwrap_or_default()
+        .chunks_exact(24)
+        .take(count)
+        .map(|point| u32_at(point, 20))
+        .collect()
+}
+
+// Loops of a smpl chunk: 36 bytes of sampler settings with the loop count at 28, then 24 bytes
+// per loop with its start and end at 8 and 12
+fn sample_loops(body: &[u8]) -> Vec<SampleLoop> {
+    let count = if body.len() >= 36 { u32_at(body, 28) as usize } else { 0 };
+    body.get(36..).unwrap_or_default()
+        .chunks_exact(24)
+        .take(count)
+        .map(|sample_loop| SampleLoop { start: u32_at(sample_loop, 8), end: u32_at(sample_loop, 12) })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunk(id: &[u8; 4], body: &[u8]) -> Vec<u8> {
+        let mut bytes = id.to_vec();
+        bytes.extend((body.len() as u32).to_le_bytes());
+        bytes.extend(body);
+        if body.len() % 2 == 1 {
+            bytes.push(0);
+        }
+        bytes
+    }
+
+    fn words(values: &[u32]) -> Vec<u8> {
+        values.iter().flat_map(|value| value.to_le_bytes()).collect()
+    }
+
+    #[test]
+    fn reads_the_title_cues_and_loops() {
+        let info = [b"INFO".to_vec(), chunk(b"IART", b"Someone\0"), chunk(b"INAM", b"Lead A\0")].concat();
+        let cues = words(&[2, 1, 0, 0, 0, 0, 100, 2, 0, 0, 0, 0, 2500]);
+        let sampler = words(&[0, 0, 22_675, 69, 0, 0, 0, 1, 0, 1, 0, 1000, 4999, 0, 0]);
+        let mut wav = b"RIFF\0\0\0\0WAVE".to_vec();
+        let chunks = [(b"fmt ", &[0; 16][..]), (b"LIST", &info), (b"data", &[0; 7]), (b"cue ", &cues)];
+        for (id, body) in chunks.into_iter().chain([(b"smpl", &sampler[..])]) {
+            wav.extend(chunk(id, body));
+        }
+
+        let metadata = read_wav_metadata_from(wav.as_slice()).unwrap();
+        assert_eq!(metadata.title.as_deref(), Some("Lead A"));
+        assert_eq!(metadata.cues, [100, 2500]);
+        assert_eq!(metadata.loops, [SampleLoop { start: 1000, end: 4999 }]);
+
+        // A chunk cut short loses only itself
+        let cut = read_wav_metadata_from(&wav[..wav.len() - 10]).unwrap();
+        assert_eq!((cut.title.as_deref(), cut.loops.len()), (Some("Lead A"), 0));
+    }
+
+    #[test]
+    fn finds_nothing_outside_a_wav_file() {
+        assert_eq!(read_wav_metadata_from(b"fLaC\0\0\0\0".as_slice()).unwrap(), WavMetadata::default());
+        assert_eq!(read_wav_metadata_from(b"RIFF\0\0\0\0WAVE".as_slice()).unwrap(), WavMetadata::default());
+    }
+}
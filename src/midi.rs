@@ -0,0 +1,145 @@
+use crate::NoteEvent;
+use midly::num::{u15, u24, u28, u4, u7};
+use midly::{Format, Header, MetaMessage, MidiMessage, Smf, Timing, TrackEvent, TrackEventKind};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::str::FromStr;
+
+// Ticks per quarter note, with the tempo fixed at 120 BPM so one second is 960 ticks
+const TICKS_PER_QUARTER: u16 = 480;
+const MICROSECONDS_PER_QUARTER: u32 = 500_000;
+const TICKS_PER_SECOND: f32 = TICKS_PER_QUARTER as f32 * 1_000_000.0 / MICROSECONDS_PER_QUARTER as f32;
+
+// RMS level of a full-scale sine, which gets the highest velocity
+const FULL_SCALE_RMS: f32 = std::f32::consts::FRAC_1_SQRT_2;
+
+// Range of levels below full scale spread over the velocities by the logarithmic curve
+const VELOCITY_RANGE_DB: f32 = 60.0;
+
+/// How a note's RMS level is mapped to its MIDI velocity.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum VelocityCurve {
+    /// Velocity proportional to the RMS level, reaching 127 at the level of a full-scale sine.
+    Linear,
+    /// Velocity proportional to the level in decibels, from 1 at 60 dB below a full-scale sine
+    /// to 127 at full scale, which follows perceived loudness more closely.
+    #[default]
+    Logarithmic,
+}
+
+impl VelocityCurve {
+    /// MIDI velocity from 1 to 127 for a note whose RMS level is `rms`.
+    pub fn velocity(self, rms: f32) -> u8 {
+        let level = rms.max(0.0) / FULL_SCALE_RMS;
+        let scaled = match self {
+            VelocityCurve::Linear => level,
+            VelocityCurve::Logarithmic => 1.0 + 20.0 * level.max(f32::MIN_POSITIVE).log10() / VELOCITY_RANGE_DB,
+        };
+        (scaled * 127.0).round().clamp(1.0, 127.0) as u8
+    }
+}
+
+impl FromStr for VelocityCurve {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "linear" => Ok(VelocityCurve::Linear),
+            "log" | "logarithmic" => Ok(VelocityCurve::Logarithmic),
+            _ => Err("expected linear or log".to_string()),
+        }
+    }
+}
+
+/// Write note events to a single-track Standard MIDI File, naming the track `title` unless it's
+/// empty.
+///
+/// Each note-on's velocity comes from the note's RMS level through `velocity`. Notes whose MIDI
+/// number falls outside `0..=127` can't be represented and are skipped.
+pub fn write_midi(notes: &[NoteEvent], velocity: VelocityCurve, title: &str, path: &Path) -> io::Result<()> {
+    let mut out = BufWriter::new(File::create(path)?);
+    write_midi_to(notes, velocity, title, &mut out)?;
+    out.flush()
+}
+
+/// Write note events as a Standard MIDI File to `out`, such as stdout, as [`write_midi`] does.
+pub fn write_midi_to<W: Write>(
+    notes: &[NoteEvent],
+    velocity: VelocityCurve,
+    title: &str,
+    out: W,
+) -> io::Result<()> {
+    // Collect note-on and note-off messages with their absolute tick positions
+    let to_ticks = |seconds: f32| (seconds.max(0.0) * TICKS_PER_SECOND).round() as u32;
+    let mut messages: Vec<(u32, MidiMessage)> = Vec::new();
+    for note in notes.iter().filter(|note| (0..=127).contains(&note.midi)) {
+        let key = u7::new(note.midi as u8);
+        messages.push((to_ticks(note.start_sec), MidiMessage::NoteOn { key, vel: u7::new(velocity.velocity(note.rms)) }));
+        messages.push((to_ticks(note.end_sec), MidiMessage::NoteOff { key, vel: u7::new(0) }));
+    }
+
+    // Order by time, releasing notes before starting new ones on the same tick
+    messages.sort_by_key(|(tick, message)| (*tick, matches!(message, MidiMessage::NoteOn { .. })));
+
+    let mut track = vec![TrackEvent {
+        delta: u28::new(0),
+        kind: TrackEventKind::Meta(MetaMessage::Tempo(u24::new(MICROSECONDS_PER_QUARTER))),
+    }];
+    if !title.is_empty() {
+        track.insert(0, TrackEvent {
+            delta: u28::new(0),
+            kind: TrackEventKind::Meta(MetaMessage::TrackName(title.as_bytes())),
+        });
+    }
+    let mut last_tick = 0;
+    for (tick, message) in messages {
+        track.push(TrackEvent {
+            delta: u28::new(tick - last_tick),
+            kind: TrackEventKind::Midi { channel: u4::new(0), message },
+        });
+        last_tick = tick;
+    }
+    track.push(TrackEvent { delta: u28::new(0), kind: TrackEventKind::Meta(MetaMessage::EndOfTrack) });
+
+    let mut smf = Smf::new(Header::new(Format::SingleTrack, Timing::Metrical(u15::new(TICKS_PER_QUARTER))));
+    smf.tracks.push(track);
+    smf.write_std(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn louder_notes_get_higher_velocities() {
+        for curve in [VelocityCurve::Linear, VelocityCurve::Logarithmic] {
+            let velocities: Vec<u8> = [0.0, 0.001, 0.01, 0.1, 0.5, FULL_SCALE_RMS, 1.0]
+                .iter()
+                .map(|&rms| curve.velocity(rms))
+                .collect();
+            assert!(velocities.windows(2).all(|pair| pair[0] <= pair[1]), "{curve:?}: {velocities:?}");
+            assert_eq!(velocities[0], 1);
+            assert_eq!(velocities[5], 127);
+            assert_eq!(velocities[6], 127);
+        }
+        // A sine at a tenth of full scale is 20 dB down, a third of the logarithmic range
+        assert_eq!(VelocityCurve::Linear.velocity(0.1 * FULL_SCALE_RMS), 13);
+        assert_eq!(VelocityCurve::Logarithmic.velocity(0.1 * FULL_SCALE_RMS), 85);
+    }
+
+    #[test]
+    fn names_the_track_after_the_title() {
+        let notes = [NoteEvent { midi: 69, start_sec: 0.0, end_sec: 0.5, rms: 0.3 }];
+        let mut bytes = Vec::new();
+        write_midi_to(&notes, VelocityCurve::Linear, "Lead A", &mut bytes).unwrap();
+        let smf = Smf::parse(&bytes).unwrap();
+        assert_eq!(smf.tracks[0][0].kind, TrackEventKind::Meta(MetaMessage::TrackName(b"Lead A")));
+
+        bytes.clear();
+        write_midi_to(&notes, VelocityCurve::Linear, "", &mut bytes).unwrap();
+        let smf = Smf::parse(&bytes).unwrap();
+        let named = |event: &TrackEvent| matches!(event.kind, TrackEventKind::Meta(MetaMessage::TrackName(_)));
+        assert!(!smf.tracks[0].iter().any(named));
+    }
+}
@@ -0,0 +1,231 @@
+use crate::{quantize, NoteEvent, Notation};
+
+const HEADER: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="no"?>
+<!DOCTYPE score-partwise PUBLIC "-//Recordare//DTD MusicXML 4.0 Partwise//EN" "http://www.musicxml.org/dtds/partwise.dtd">
+<score-partwise version="4.0">
+"#;
+const PART_LIST: &str = r#"  <part-list>
+    <score-part id="P1">
+      <part-name>Transcription</part-name>
+    </score-part>
+  </part-list>
+  <part id="P1">
+"#;
+
+/// Write note events as a minimal single-part MusicXML score titled `title`, in 4/4 with
+/// quarter-note beats at `bpm`, treble clef and no key signature.
+///
+/// Onsets and lengths come from [`quantize`] on a grid of `grid` steps per whole note (16 for
+/// sixteenths), which the divisions of the score resolve exactly; gaps between notes become
+/// rests and notes crossing a bar line are tied across it. Overlapping notes are played one after
+/// the other. Pitches are spelled with sharps, MIDI 60 being C4.
+pub fn to_musicxml(notes: &[NoteEvent], bpm: f32, grid: u32, title: &str) -> String {
+    // The fewest divisions per quarter note that make a grid step a whole number of them
+    let grid = grid.max(1) as usize;
+    let divisions = match grid % 4 {
+        0 => grid / 4,
+        2 => grid / 2,
+        _ => grid,
+    };
+    let to_divisions = |beats: f32| (beats * divisions as f32).round() as usize;
+
+    let mut xml = HEADER.to_string();
+    xml.push_str(&format!("  <work>\n    <work-title>{}</work-title>\n  </work>\n", xml_escape(title)));
+    xml.push_str(PART_LIST);
+    let mut measure = Measure { position: 0, divisions };
+    measure.open(&mut xml, bpm);
+    for (note, quantized) in notes.iter().zip(quantize(notes, bpm, grid as u32)) {
+        let start = to_divisions(quantized.start_beat).max(measure.position);
+        let end = start + to_divisions(quantized.beats);
+        measure.push(&mut xml, None, start - measure.position, bpm);
+        measure.push(&mut xml, Some(note.midi), end - start, bpm);
+    }
+    let per_measure = measure.per_measure();
+    match measure.position {
+        0 => xml.push_str(&format!(
+            "      <note>\n        <rest measure=\"yes\"/>\n        <duration>{}</duration>\n      </note>\n",
+            per_measure
+        )),
+        // Fill the last measure with a rest
+        position => {
+            let rest = (per_measure - position % per_measure) % per_measure;
+            measure.push(&mut xml, None, rest, bpm);
+        }
+    }
+    if measure.position == 0 {
+        xml.push_str("    </measure>\n");
+    }
+    xml.push_str("  </part>\n</score-partwise>\n");
+    xml
+}
+
+// `text` with the characters that XML gives a meaning escaped
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+// MusicXML step, alter and octave of a MIDI note number, e.g. ("C", 1, 4) for 61
+fn musicxml_pitch(midi: i32) -> (&'static str, i32, i32) {
+    let name = Notation::EnglishSharp.pitch_class(midi);
+    let octave = midi.div_euclid(12) - 1;
+    match name.strip_suffix('#') {
+        Some(step) => (step, 1, octave),
+        None => (name, 0, octave),
+    }
+}
+
+// Tracks the position in divisions while writing notes, opening and closing measures and tying
+// notes across bar lines
+struct Measure {
+    position: usize,
+    // Divisions per quarter note
+    divisions: usize,
+}
+
+impl Measure {
+    // Divisions per 4/4 measure
+    fn per_measure(&self) -> usize {
+        4 * self.divisions
+    }
+
+    // Open the measure starting at the current position; the first one also carries the time
+    // signature, clef and tempo
+    fn open(&self, xml: &mut String, bpm: f32) {
+        let number = self.position / self.per_measure() + 1;
+        xml.push_str(&format!("    <measure number=\"{number}\">\n"));
+        if number == 1 {
+            let (divisions, tempo) = (self.divisions, bpm.round());
+            xml.push_str(&format!(
+                "      <attributes>
+        <divisions>{divisions}</divisions>
+        <key>
+          <fifths>0</fifths>
+        </key>
+        <time>
+          <beats>4</beats>
+          <beat-type>4</beat-type>
+        </time>
+        <clef>
+          <sign>G</sign>
+          <line>2</line>
+        </clef>
+      </attributes>
+      <direction placement=\"above\">
+        <direction-type>
+          <metronome>
+            <beat-unit>quarter</beat-unit>
+            <per-minute>{tempo}</per-minute>
+          </metronome>
+        </direction-type>
+        <sound tempo=\"{tempo}\"/>
+      </direction>
+"
+            ));
+        }
+    }
+
+    // Write a note of `midi` (or a rest for `None`) lasting `length` divisions, split at bar lines
+    fn push(&mut self, xml: &mut String, midi: Option<i32>, mut length: usize, bpm: f32) {
+        let mut tied = false;
+        while length > 0 {
+            if self.position > 0 && self.position.is_multiple_of(self.per_measure()) {
+                self.open(xml, bpm);
+            }
+            let part = length.min(self.per_measure() - self.position % self.per_measure());
+            length -= part;
+            self.position += part;
+
+            xml.push_str("      <note>\n");
+            match midi {
+                Some(midi) => {
+                    let (step, alter, octave) = musicxml_pitch(midi);
+                    xml.push_str(&format!("        <pitch>\n          <step>{step}</step>\n"));
+                    if alter != 0 {
+                        xml.push_str(&format!("          <alter>{alter}</alter>\n"));
+                    }
+                    xml.push_str(&format!("          <octave>{octave}</octave>\n        </pitch>\n"));
+                }
+                None => xml.push_str("        <rest/>\n"),
+            }
+            xml.push_str(&format!("        <duration>{part}</duration>\n"));
+            let ties: Vec<&str> = [(tied, "stop"), (length > 0, "start")]
+                .into_iter()
+                .filter(|&(tie, _)| tie && midi.is_some())
+                .map(|(_, kind)| kind)
+                .collect();
+            for tie in &ties {
+                xml.push_str(&format!("        <tie type=\"{tie}\"/>\n"));
+            }
+            if !ties.is_empty() {
+                xml.push_str("        <notations>\n");
+                for tie in &ties {
+                    xml.push_str(&format!("          <tied type=\"{tie}\"/>\n"));
+                }
+                xml.push_str("        </notations>\n");
+            }
+            xml.push_str("      </note>\n");
+            tied = true;
+
+            if self.position.is_multiple_of(self.per_measure()) {
+                xml.push_str("    </measure>\n");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::note;
+
+    #[test]
+    fn spells_pitches_with_step_alter_and_octave() {
+        assert_eq!(musicxml_pitch(60), ("C", 0, 4));
+        assert_eq!(musicxml_pitch(61), ("C", 1, 4));
+        assert_eq!(musicxml_pitch(69), ("A", 0, 4));
+        assert_eq!(musicxml_pitch(47), ("B", 0, 2));
+        assert_eq!(musicxml_pitch(82), ("A", 1, 5));
+    }
+
+    #[test]
+    fn writes_measures_with_rests_and_ties() {
+        // At 120 BPM a sixteenth lasts 0.125 s and a measure 2 s
+        let notes = [note(69, 0.0, 0.5), note(61, 1.0, 2.5)];
+        let xml = to_musicxml(&notes, 120.0, 16, "Scales & <Arpeggios>");
+        assert!(xml.contains("<work-title>Scales &amp; &lt;Arpeggios&gt;</work-title>"), "{xml}");
+        assert!(xml.contains("<per-minute>120</per-minute>"), "{xml}");
+        assert_eq!(xml.matches("<measure number=").count(), 2, "{xml}");
+        let a4 = "<step>A</step>\n          <octave>4</octave>\n        </pitch>\n        <duration>4</duration>";
+        assert!(xml.contains(a4), "{xml}");
+        assert!(xml.contains("<rest/>\n        <duration>4</duration>"), "{xml}");
+        assert!(xml.contains("<alter>1</alter>"), "{xml}");
+        // The C# lasts 12 sixteenths, 8 of them in the first measure, tied into the second
+        assert!(xml.contains("<duration>8</duration>\n        <tie type=\"start\"/>"), "{xml}");
+        assert!(xml.contains("<duration>4</duration>\n        <tie type=\"stop\"/>"), "{xml}");
+        // The rest of the second measure is a rest
+        let end = "<rest/>\n        <duration>12</duration>\n      </note>\n    </measure>\n  </part>\n</score-partwise>\n";
+        assert!(xml.ends_with(end), "{xml}");
+        assert_eq!(xml.matches("<measure").count(), xml.matches("</measure>").count());
+    }
+
+    #[test]
+    fn resolves_the_grid_it_is_given() {
+        // At 120 BPM a thirty-second lasts 0.0625 s, a single division once a quarter has eight
+        let notes = [note(60, 0.0, 0.0625), note(62, 0.0625, 0.25)];
+        let xml = to_musicxml(&notes, 120.0, 32, "Thirty-seconds");
+        assert!(xml.contains("<divisions>8</divisions>"), "{xml}");
+        let c4 = "<step>C</step>\n          <octave>4</octave>\n        </pitch>\n        <duration>1</duration>";
+        assert!(xml.contains(c4), "{xml}");
+        assert!(xml.contains("<octave>4</octave>\n        </pitch>\n        <duration>3</duration>"), "{xml}");
+        // Eighth-note triplets need three divisions per quarter
+        assert!(to_musicxml(&notes, 120.0, 12, "Triplets").contains("<divisions>3</divisions>"));
+    }
+
+    #[test]
+    fn writes_a_whole_rest_without_notes() {
+        let xml = to_musicxml(&[], 90.0, 16, "Silence");
+        assert!(xml.contains("<measure number=\"1\">"), "{xml}");
+        assert!(xml.contains("<rest measure=\"yes\"/>"), "{xml}");
+        assert!(xml.ends_with("</measure>\n  </part>\n</score-partwise>\n"), "{xml}");
+    }
+}
@@ -0,0 +1,92 @@
+use std::str::FromStr;
+
+const SHARP_NAMES: [&str; 12] = ["C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B"];
+const FLAT_NAMES: [&str; 12] = ["C", "Db", "D", "Eb", "E", "F", "Gb", "G", "Ab", "A", "Bb", "B"];
+const GERMAN_NAMES: [&str; 12] = ["C", "Cis", "D", "Dis", "E", "F", "Fis", "G", "Gis", "A", "B", "H"];
+const SOLFEGE_NAMES: [&str; 12] = ["Do", "Do#", "Re", "Re#", "Mi", "Fa", "Fa#", "Sol", "Sol#", "La", "La#", "Si"];
+
+/// Convention for naming notes. Octave numbers are the same in all of them (A4 = MIDI 69).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Notation {
+    /// C, C#, D, ... B.
+    #[default]
+    EnglishSharp,
+    /// C, Db, D, ... B.
+    EnglishFlat,
+    /// C, Cis, D, ... A, B, H, where B is B flat and H is B natural.
+    German,
+    /// Fixed-do solfège: Do, Do#, Re, ... Si.
+    Solfege,
+}
+
+impl Notation {
+    /// Name of a MIDI note number's pitch class, without the octave, e.g. `A` for 69.
+    pub fn pitch_class(self, midi: i32) -> &'static str {
+        let names = match self {
+            Notation::EnglishSharp => &SHARP_NAMES,
+            Notation::EnglishFlat => &FLAT_NAMES,
+            Notation::German => &GERMAN_NAMES,
+            Notation::Solfege => &SOLFEGE_NAMES,
+        };
+        names[midi.rem_euclid(12) as usize]
+    }
+
+    /// Name of a MIDI note number, e.g. `A4` for 69.
+    pub fn note_name(self, midi: i32) -> String {
+        self.note_name_with_offset(midi, 0)
+    }
+
+    /// Name of a MIDI note number with `octave_offset` added to its octave number, for software
+    /// that calls middle C C5 (offset 1) or C3 (offset -1) instead of C4, e.g. `A5` for 69 with
+    /// an offset of 1.
+    pub fn note_name_with_offset(self, midi: i32, octave_offset: i32) -> String {
+        // Round the octave down so notes below C-1 don't go negative
+        let octave = midi.div_euclid(12) - 1; // Octave adjustment for MIDI standard
+        format!("{}{}", self.pitch_class(midi), octave + octave_offset)
+    }
+}
+
+impl FromStr for Notation {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "english" | "english-sharp" => Ok(Notation::EnglishSharp),
+            "english-flat" => Ok(Notation::EnglishFlat),
+            "german" => Ok(Notation::German),
+            "solfege" => Ok(Notation::Solfege),
+            _ => Err("expected english-sharp, english-flat, german or solfege".to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_b_flat_b_and_h_in_german() {
+        assert_eq!(Notation::German.note_name(70), "B4");
+        assert_eq!(Notation::German.note_name(71), "H4");
+        assert_eq!(Notation::German.note_name(61), "Cis4");
+        assert_eq!(Notation::EnglishSharp.note_name(70), "A#4");
+        assert_eq!(Notation::EnglishFlat.note_name(70), "Bb4");
+    }
+
+    #[test]
+    fn names_fixed_do_solfege() {
+        assert_eq!(Notation::Solfege.note_name(60), "Do4");
+        assert_eq!(Notation::Solfege.note_name(67), "Sol4");
+        assert_eq!(Notation::Solfege.note_name(71), "Si4");
+    }
+
+    #[test]
+    fn parses_the_names_listed_in_its_error() {
+        assert_eq!("german".parse(), Ok(Notation::German));
+        assert_eq!("solfege".parse(), Ok(Notation::Solfege));
+        assert_eq!("english-flat".parse(), Ok(Notation::EnglishFlat));
+        assert_eq!("english".parse(), Ok(Notation::EnglishSharp));
+        assert_eq!("english-sharp".parse(), Ok(Notation::EnglishSharp));
+        assert!("German".parse::<Notation>().is_err());
+    }
+}
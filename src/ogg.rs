@@ -0,0 +1,80 @@
+use crate::{Audio, Error};
+use lewton::inside_ogg::OggStreamReader;
+use lewton::samples::InterleavedSamples;
+use std::fs::File;
+use std::io::{BufReader, Read, Seek};
+use std::path::Path;
+
+/// Read an Ogg Vorbis file without downmixing it.
+///
+/// Vorbis decodes to floating-point samples, which are kept as they are in `[-1.0, 1.0]`, like
+/// float WAV samples.
+pub fn read_ogg(path: &Path) -> Result<Audio, Error> {
+    let file = File::open(path).map_err(Error::FileOpen)?;
+    read_ogg_from(BufReader::new(file))
+}
+
+/// Read an Ogg Vorbis stream without downmixing it.
+pub fn read_ogg_from<R: Read + Seek>(reader: R) -> Result<Audio, Error> {
+    let mut reader = OggStreamReader::new(reader)?;
+    let mut samples = Vec::new();
+    while let Some(packet) = reader.read_dec_packet_generic::<InterleavedSamples<f32>>()? {
+        samples.extend(packet.samples);
+    }
+    if samples.is_empty() {
+        return Err(Error::EmptyFile);
+    }
+
+    Ok(Audio {
+        samples,
+        channels: reader.ident_hdr.audio_channels as usize,
+        sample_rate: reader.ident_hdr.audio_sample_rate,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{mix, wav};
+    use std::io::Cursor;
+
+    // 1 s of E4 (329.63 Hz) at half scale, 22.05 kHz mono, as 16-bit samples and as the Vorbis
+    // file tests/data/e4.ogg encoded from them
+    const E4_OGG: &[u8] = include_bytes!("../tests/data/e4.ogg");
+    // 1 s of A4 at half scale in the left channel and silence in the right, 22.05 kHz
+    const A4_LEFT_OGG: &[u8] = include_bytes!("../tests/data/a4_left.ogg");
+
+    #[test]
+    fn detects_the_same_note_as_the_wav() {
+        let ogg = read_ogg_from(Cursor::new(E4_OGG)).unwrap();
+        let e4 = mix(&[(329.63, 0.5)], 22_050, 22_050);
+        let wav = crate::read_wav_from(Cursor::new(wav(&e4, 1, 22_050, 16))).unwrap();
+        assert_eq!((ogg.channels, ogg.sample_rate), (1, 22_050));
+        let settings = crate::Settings::default();
+        let from_ogg = crate::analyze(&ogg.mono(), ogg.sample_rate, &settings).unwrap();
+        let from_wav = crate::analyze(&wav.mono(), wav.sample_rate, &settings).unwrap();
+        let (ogg_bin, wav_bin) = (from_ogg.max_index.unwrap(), from_wav.max_index.unwrap());
+        assert!(ogg_bin.abs_diff(wav_bin) <= 1, "bin {} from the Ogg file, {} from the WAV", ogg_bin, wav_bin);
+        let note = from_ogg.note(crate::A4_HZ, crate::Notation::default()).unwrap();
+        assert_eq!(note.note, "E4");
+    }
+
+    #[test]
+    fn keeps_the_channels_apart() {
+        let audio = read_ogg_from(Cursor::new(A4_LEFT_OGG)).unwrap();
+        assert_eq!(audio.channels, 2);
+        assert!((crate::rms(&audio.channel(0).unwrap()) - 0.5 / 2f32.sqrt()).abs() < 0.02);
+        assert!(crate::rms(&audio.channel(1).unwrap()) < 0.01);
+        // Mixed down, the tone is at half its level in the left channel
+        let mono = audio.mono();
+        assert!((crate::rms(&mono) - 0.25 / 2f32.sqrt()).abs() < 0.02, "{}", crate::rms(&mono));
+        let analysis = crate::analyze(&mono, audio.sample_rate, &crate::Settings::default()).unwrap();
+        assert_eq!(analysis.note(crate::A4_HZ, crate::Notation::default()).unwrap().note, "A4");
+    }
+
+    #[test]
+    fn rejects_a_stream_that_isnt_ogg() {
+        let result = read_ogg_from(Cursor::new(b"RIFF\0\0\0\0WAVEfmt ".to_vec()));
+        assert!(matches!(result, Err(Error::Ogg(_))), "{:?}", result);
+    }
+}
@@ -0,0 +1,73 @@
+/// Spectral flux of a spectrogram: for each frame, the summed increase in magnitude over the
+/// previous frame, counting only bins that grew.
+///
+/// Onsets bring energy into many bins at once, so the flux peaks at each onset while steady
+/// notes and decays contribute little. The first frame has no predecessor and gets 0.0.
+pub fn spectral_flux(frames: &[Vec<f32>]) -> Vec<f32> {
+    let mut flux = Vec::with_capacity(frames.len());
+    if !frames.is_empty() {
+        flux.push(0.0);
+    }
+    flux.extend(frames.windows(2).map(|pair| {
+        pair[1].iter().zip(&pair[0]).map(|(&current, &previous)| (current - previous).max(0.0)).sum::<f32>()
+    }));
+    flux
+}
+
+// Frames on each side a flux peak must be the largest of, and over which the local mean that it
+// must rise above is taken
+const PEAK_RADIUS: usize = 3;
+const MEAN_RADIUS: usize = 8;
+
+// How far above the local mean a peak must rise, as a fraction of the strongest flux
+const PEAK_DELTA: f32 = 0.1;
+
+// Shortest gap between two onsets; within it only the first counts
+const MIN_ONSET_GAP_SEC: f32 = 0.05;
+
+/// Onset times in seconds picked from a [`spectral_flux`] envelope whose frames lie `hop` samples
+/// apart at `rate`.
+///
+/// A frame is an onset when its flux is the largest within three frames either side and exceeds
+/// the mean of the eight frames either side by a tenth of the strongest flux, so the threshold
+/// follows the music's loudness. Onsets less than 50 ms after the previous one are dropped.
+/// Silence has no onsets.
+pub fn detect_onsets(flux: &[f32], hop: usize, rate: f32) -> Vec<f32> {
+    let strongest = flux.iter().cloned().fold(0.0, f32::max);
+    if strongest <= 0.0 {
+        return Vec::new();
+    }
+    let min_gap = (MIN_ONSET_GAP_SEC * rate / hop as f32).ceil() as usize;
+    let around = |i: usize, radius: usize| &flux[i.saturating_sub(radius)..(i + radius + 1).min(flux.len())];
+
+    let mut onsets: Vec<usize> = Vec::new();
+    for (i, &value) in flux.iter().enumerate() {
+        let is_peak = around(i, PEAK_RADIUS).iter().all(|&other| other <= value);
+        let neighbourhood = around(i, MEAN_RADIUS);
+        let mean = neighbourhood.iter().sum::<f32>() / neighbourhood.len() as f32;
+        let after_gap = onsets.last().is_none_or(|&last| i - last >= min_gap);
+        if is_peak && value > mean + PEAK_DELTA * strongest && after_gap {
+            onsets.push(i);
+        }
+    }
+    onsets.into_iter().map(|i| (i * hop) as f32 / rate).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn picks_peaks_above_the_local_mean() {
+        // 10 ms frames with onsets at frames 10, 30 and a weaker one at 50, a ripple at 70, and a
+        // peak at 14 that follows the first onset too closely
+        let mut flux = vec![0.0; 100];
+        flux[10] = 1.0;
+        flux[14] = 0.6;
+        flux[30] = 0.8;
+        flux[50] = 0.3;
+        flux[70] = 0.05;
+        assert_eq!(detect_onsets(&flux, 10, 1000.0), [0.1, 0.3, 0.5]);
+        assert!(detect_onsets(&[0.0; 100], 100, 1000.0).is_empty());
+    }
+}
@@ -0,0 +1,111 @@
+/// Default fraction of the largest magnitude a peak must reach to count as a chord tone, which
+/// leaves out the window's sidelobes around each tone.
+pub const POLY_THRESHOLD: f32 = 0.05;
+
+/// Default factor by which a peak must exceed the [`noise_floor`] to count as a tone, about 18 dB.
+pub const NOISE_FLOOR_FACTOR: f32 = 8.0;
+
+/// Default maximum number of peaks considered for a chord.
+pub const POLY_MAX_PEAKS: usize = 8;
+
+/// How far in cents a peak may be from a whole multiple of a lower fundamental and still count as
+/// one of its harmonics in [`fundamentals`].
+pub const HARMONIC_TOLERANCE_CENTS: f32 = 30.0;
+
+/// Typical magnitude of the bins between tones: the median of `magnitudes`, or 0.0 for none.
+///
+/// Tones occupy only a few bins each, so the median follows the noise rather than the tones
+/// however loud they are.
+pub fn noise_floor(magnitudes: &[f32]) -> f32 {
+    if magnitudes.is_empty() {
+        return 0.0;
+    }
+    let mut sorted = magnitudes.to_vec();
+    let middle = sorted.len() / 2;
+    let (_, &mut median, _) = sorted.select_nth_unstable_by(middle, f32::total_cmp);
+    median
+}
+
+/// Indices of the local maxima of `magnitudes` that reach `threshold_ratio` of the global maximum.
+///
+/// At most `max_peaks` indices are returned, strongest first.
+pub fn find_peaks(magnitudes: &[f32], threshold_ratio: f32, max_peaks: usize) -> Vec<usize> {
+    let global_max = magnitudes.iter().cloned().fold(0.0, f32::max);
+    if global_max <= 0.0 {
+        return Vec::new();
+    }
+    find_peaks_above(magnitudes, threshold_ratio * global_max, max_peaks)
+}
+
+/// Indices of the local maxima of `magnitudes` that reach `threshold`, such as a multiple of the
+/// [`noise_floor`].
+///
+/// At most `max_peaks` indices are returned, strongest first.
+pub fn find_peaks_above(magnitudes: &[f32], threshold: f32, max_peaks: usize) -> Vec<usize> {
+    // A bin is a local maximum when it rises above its left neighbour and isn't below its right
+    let mut peaks: Vec<usize> = (0..magnitudes.len())
+        .filter(|&i| {
+            let left = if i == 0 { f32::MIN } else { magnitudes[i - 1] };
+            let right = magnitudes.get(i + 1).cloned().unwrap_or(f32::MIN);
+            magnitudes[i] >= threshold && magnitudes[i] > left && magnitudes[i] >= right
+        })
+        .collect();
+
+    peaks.sort_by(|&a, &b| magnitudes[b].total_cmp(&magnitudes[a]));
+    peaks.truncate(max_peaks);
+    peaks
+}
+
+/// The peak frequencies that aren't harmonics of a lower one, lowest first.
+///
+/// Going up from the lowest, a frequency within [`HARMONIC_TOLERANCE_CENTS`] of a whole multiple
+/// of a fundamental already found is taken as that note's overtone; any other starts a new
+/// fundamental. A note an octave or a twelfth above another is therefore hidden in its overtones
+/// and not counted.
+pub fn fundamentals(frequencies: &[f32]) -> Vec<f32> {
+    let mut sorted: Vec<f32> = frequencies.iter().copied().filter(|&frequency| frequency > 0.0).collect();
+    sorted.sort_by(f32::total_cmp);
+    let mut found: Vec<f32> = Vec::new();
+    for frequency in sorted {
+        let is_harmonic = found.iter().any(|&fundamental| {
+            let multiple = (frequency / fundamental).round().max(1.0);
+            (1200.0 * (frequency / (multiple * fundamental)).log2()).abs() <= HARMONIC_TOLERANCE_CENTS
+        });
+        if !is_harmonic {
+            found.push(frequency);
+        }
+    }
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn groups_harmonics_under_their_fundamentals() {
+        // C4 and E4 with two harmonics each, and G4 on its own
+        let peaks = [523.3, 261.6, 329.6, 659.3, 784.9, 392.0, 989.0];
+        assert_eq!(fundamentals(&peaks), [261.6, 329.6, 392.0]);
+        // Slightly sharp harmonics still belong to their fundamental
+        assert_eq!(fundamentals(&[110.0, 221.0, 331.5]), [110.0]);
+        assert!(fundamentals(&[]).is_empty());
+    }
+
+    #[test]
+    fn floor_threshold_follows_the_noise() {
+        // The same two tones over a quiet and a loud noise floor
+        let spectrum = |noise: f32| -> Vec<f32> {
+            let mut magnitudes: Vec<f32> = (0..100).map(|i| noise * (1.0 + 0.1 * (i % 3) as f32)).collect();
+            magnitudes[20] = 10.0;
+            magnitudes[60] = 1.0;
+            magnitudes
+        };
+        let clean = spectrum(0.01);
+        assert_eq!(noise_floor(&clean), 0.011);
+        assert_eq!(find_peaks_above(&clean, noise_floor(&clean) * NOISE_FLOOR_FACTOR, 8), [20, 60]);
+        let noisy = spectrum(0.5);
+        assert_eq!(find_peaks_above(&noisy, noise_floor(&noisy) * NOISE_FLOOR_FACTOR, 8), [20]);
+        assert_eq!(noise_floor(&[]), 0.0);
+    }
+}
@@ -0,0 +1,54 @@
+use crate::{apply_window, Window};
+use std::f64::consts::{PI, TAU};
+
+// Two frames this far apart, as a fraction of the samples, are compared: a quarter of each
+// frame's length, so a coarse estimate less than 2 bins off still unwraps to the right frequency
+const HOP_FRACTION: usize = 5;
+
+/// Refine `coarse_hz`, the frequency of a peak found by other means, from the phase advance of
+/// the signal at that frequency between two Hann-windowed frames a quarter of a frame apart
+/// (the phase vocoder's instantaneous frequency).
+///
+/// For a steady, dominant tone this is far finer than any interpolation between FFT bins,
+/// within a few millihertz; `coarse_hz` only has to lie within 2 of the frame's bins of
+/// it. Returns `None` when there are too few samples or no energy at `coarse_hz`.
+pub fn instantaneous_frequency(samples: &[f32], sample_rate: f32, coarse_hz: f32) -> Option<f32> {
+    let hop = samples.len() / HOP_FRACTION;
+    if hop == 0 {
+        return None;
+    }
+    let frame = samples.len() - hop;
+    let omega = TAU * coarse_hz as f64 / sample_rate as f64;
+    let phase = |start: usize| {
+        let (windowed, _) = apply_window(&samples[start..start + frame], Window::Hann);
+        // The spectrum at `omega` alone, summed in f64 so long frames keep their phase accurate
+        let (re, im) = windowed.iter().enumerate().fold((0.0, 0.0), |(re, im), (n, &sample)| {
+            let angle = omega * n as f64;
+            (re + sample as f64 * angle.cos(), im - sample as f64 * angle.sin())
+        });
+        (re != 0.0 || im != 0.0).then(|| im.atan2(re))
+    };
+    let advance = phase(hop)? - phase(0)?;
+
+    // The advance a tone at exactly `coarse_hz` would make, and how far the measured one differs
+    // from it, wrapped to a half turn either way
+    let expected = omega * hop as f64;
+    let deviation = (advance - expected + PI).rem_euclid(TAU) - PI;
+    Some(((expected + deviation) / hop as f64 * sample_rate as f64 / TAU) as f32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::sine;
+
+    #[test]
+    fn refines_a_coarse_estimate_to_the_exact_frequency() {
+        for (frequency, coarse) in [(1000.0, 999.6), (1000.37, 1001.0), (61.7, 62.0)] {
+            let refined = instantaneous_frequency(&sine(frequency, 8192, 8000), 8000.0, coarse).unwrap();
+            assert!((refined - frequency).abs() < 0.01, "{} Hz refined to {} Hz", frequency, refined);
+        }
+        assert_eq!(instantaneous_frequency(&[0.0; 4], 8000.0, 440.0), None);
+        assert_eq!(instantaneous_frequency(&[0.0; 1000], 8000.0, 440.0), None);
+    }
+}
@@ -0,0 +1,55 @@
+use realfft::{ComplexToReal, RealFftPlanner, RealToComplex};
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, Mutex, PoisonError};
+
+/// Forward and inverse real FFTs planned once per size and shared between calls and threads.
+#[derive(Default)]
+pub(crate) struct FftCache {
+    plans: Mutex<HashMap<usize, Arc<dyn RealToComplex<f32>>>>,
+    inverse_plans: Mutex<HashMap<usize, Arc<dyn ComplexToReal<f32>>>>,
+}
+
+impl FftCache {
+    /// The forward FFT of `size` points, planned on first use.
+    pub(crate) fn forward(&self, size: usize) -> Arc<dyn RealToComplex<f32>> {
+        // A panic elsewhere can't leave the map half-updated, so a poisoned lock is still usable
+        let mut plans = self.plans.lock().unwrap_or_else(PoisonError::into_inner);
+        plans.entry(size)
+            .or_insert_with(|| RealFftPlanner::<f32>::new().plan_fft_forward(size))
+            .clone()
+    }
+
+    /// The inverse FFT of `size` points, planned on first use.
+    pub(crate) fn inverse(&self, size: usize) -> Arc<dyn ComplexToReal<f32>> {
+        let mut plans = self.inverse_plans.lock().unwrap_or_else(PoisonError::into_inner);
+        plans.entry(size)
+            .or_insert_with(|| RealFftPlanner::<f32>::new().plan_fft_inverse(size))
+            .clone()
+    }
+}
+
+// The sizes planned in `plans`, smallest first
+fn sizes<T: ?Sized>(plans: &Mutex<HashMap<usize, Arc<T>>>) -> Vec<usize> {
+    let plans = plans.lock().unwrap_or_else(PoisonError::into_inner);
+    let mut sizes: Vec<usize> = plans.keys().copied().collect();
+    sizes.sort();
+    sizes
+}
+
+impl Clone for FftCache {
+    fn clone(&self) -> Self {
+        let plans = self.plans.lock().unwrap_or_else(PoisonError::into_inner).clone();
+        let inverse_plans = self.inverse_plans.lock().unwrap_or_else(PoisonError::into_inner).clone();
+        FftCache { plans: Mutex::new(plans), inverse_plans: Mutex::new(inverse_plans) }
+    }
+}
+
+impl fmt::Debug for FftCache {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("FftCache")
+            .field("sizes", &sizes(&self.plans))
+            .field("inverse_sizes", &sizes(&self.inverse_plans))
+            .finish()
+    }
+}
@@ -0,0 +1,63 @@
+use crate::NoteEvent;
+use serde::Serialize;
+
+/// Default grid resolution for [`quantize`]: sixteenth notes.
+pub const DEFAULT_GRID: u32 = 16;
+
+/// A note's onset and length snapped to a tempo grid, in beats (quarter notes).
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct QuantizedNote {
+    /// Onset in beats from the start of the recording, the first beat being 0.
+    pub start_beat: f32,
+    /// Length in beats, at least one grid step.
+    pub beats: f32,
+}
+
+impl QuantizedNote {
+    /// `note` with its onset and release moved onto the grid at `bpm`.
+    pub fn snap(&self, note: &NoteEvent, bpm: f32) -> NoteEvent {
+        let seconds_per_beat = 60.0 / bpm;
+        NoteEvent {
+            start_sec: self.start_beat * seconds_per_beat,
+            end_sec: (self.start_beat + self.beats) * seconds_per_beat,
+            ..note.clone()
+        }
+    }
+}
+
+/// Snap each note's onset and duration to the nearest step of a grid of `grid` steps per whole
+/// note (16 for sixteenth notes) at `bpm` quarter notes per minute.
+///
+/// Durations are rounded separately from onsets, so a note keeps its length even when its onset
+/// moves, and no note is shorter than one step. Returns one entry per note, in the same order.
+pub fn quantize(notes: &[NoteEvent], bpm: f32, grid: u32) -> Vec<QuantizedNote> {
+    let step = 4.0 / grid as f32;
+    let to_steps = |seconds: f32| (seconds.max(0.0) * bpm / 60.0 / step).round();
+    notes.iter()
+        .map(|note| QuantizedNote {
+            start_beat: to_steps(note.start_sec) * step,
+            beats: to_steps(note.duration()).max(1.0) * step,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::note;
+
+    #[test]
+    fn snaps_to_the_nearest_step() {
+        // At 120 BPM a beat lasts 0.5 s and a sixteenth 0.125 s
+        let notes = [note(60, 0.02, 0.49), note(60, 0.51, 0.56), note(60, 1.19, 1.97)];
+        let quantized = quantize(&notes, 120.0, 16);
+        let beats: Vec<(f32, f32)> = quantized.iter().map(|q| (q.start_beat, q.beats)).collect();
+        assert_eq!(beats, [(0.0, 1.0), (1.0, 0.25), (2.5, 1.5)]);
+
+        let eighths = quantize(&notes, 120.0, 8);
+        assert_eq!((eighths[1].start_beat, eighths[1].beats), (1.0, 0.5));
+
+        let snapped = quantized[2].snap(&notes[2], 120.0);
+        assert_eq!((snapped.start_sec, snapped.end_sec), (1.25, 2.0));
+    }
+}
@@ -0,0 +1,159 @@
+use crate::{frequency_to_midi, NoteEvent};
+
+/// Group per-frame pitches into note events.
+///
+/// Consecutive frames that round to the same MIDI number, with A4 tuned to `a4_hz`, form one
+/// note; a note ends when the pitch moves to a different MIDI number or a frame is silent
+/// (`None`). Frame `i` is taken to start at `i * hop / sample_rate` seconds, and a note lasts
+/// until the start of the frame after its last one. Each note's level combines the `frame_rms`
+/// of its frames; frames without a level count as silent.
+pub fn segment_notes(
+    frame_pitches: &[Option<f32>],
+    frame_rms: &[f32],
+    hop: usize,
+    sample_rate: f32,
+    a4_hz: f32,
+) -> Vec<NoteEvent> {
+    let mut segmenter = Segmenter::new(hop, sample_rate, a4_hz);
+    let mut notes: Vec<NoteEvent> = frame_pitches.iter()
+        .enumerate()
+        .filter_map(|(frame, &pitch)| segmenter.push(pitch, frame_rms.get(frame).copied().unwrap_or(0.0)))
+        .collect();
+    notes.extend(segmenter.finish());
+    notes
+}
+
+/// Drop the notes shorter than `min_sec`, which are mostly spurious: brief jumps of the pitch
+/// track at onsets or between two notes. Real notes that short, such as quick staccato notes or
+/// grace notes, are lost too.
+///
+/// With `merge`, a short note next to a note of the same pitch is absorbed into it instead, and
+/// two notes of the same pitch that end up next to each other once the short notes between them
+/// are dropped become one. A merged note spans both and its level combines theirs.
+pub fn drop_short_notes(notes: &[NoteEvent], min_sec: f32, merge: bool) -> Vec<NoteEvent> {
+    let is_short = |note: &NoteEvent| note.duration() < min_sec;
+    let mut kept: Vec<NoteEvent> = Vec::new();
+    // Whether a note was dropped since the last one kept, and a short note waiting to be absorbed
+    // into the next one
+    let mut dropped = false;
+    let mut pending: Option<NoteEvent> = None;
+    for (index, note) in notes.iter().enumerate() {
+        let last = kept.last_mut().filter(|last| merge && last.midi == note.midi);
+        if is_short(note) {
+            if let Some(last) = last {
+                absorb(last, note);
+            } else if merge && notes.get(index + 1).is_some_and(|next| next.midi == note.midi && !is_short(next)) {
+                pending = Some(note.clone());
+            } else {
+                dropped = true;
+            }
+            continue;
+        }
+        match last {
+            Some(last) if dropped => absorb(last, note),
+            _ => {
+                let mut note = note.clone();
+                if let Some(short) = pending.take() {
+                    absorb(&mut note, &short);
+                }
+                kept.push(note);
+            }
+        }
+        dropped = false;
+    }
+    kept
+}
+
+// Extend `into` over `note`'s span, combining their levels weighted by duration
+fn absorb(into: &mut NoteEvent, note: &NoteEvent) {
+    let energy = into.rms * into.rms * into.duration() + note.rms * note.rms * note.duration();
+    let duration = into.duration() + note.duration();
+    into.start_sec = into.start_sec.min(note.start_sec);
+    into.end_sec = into.end_sec.max(note.end_sec);
+    into.rms = if duration > 0.0 { (energy / duration).sqrt() } else { into.rms };
+}
+
+// Builds note events one frame at a time, as `segment_notes` describes
+pub(crate) struct Segmenter {
+    hop: usize,
+    sample_rate: f32,
+    a4_hz: f32,
+    frames: usize,
+    // MIDI number, first frame and summed squared frame levels of the open note
+    current: Option<(i32, usize, f32)>,
+}
+
+impl Segmenter {
+    pub(crate) fn new(hop: usize, sample_rate: f32, a4_hz: f32) -> Self {
+        Segmenter { hop, sample_rate, a4_hz, frames: 0, current: None }
+    }
+
+    // Add the next frame, returning the note it ends, if any
+    pub(crate) fn push(&mut self, pitch: Option<f32>, rms: f32) -> Option<NoteEvent> {
+        let frame = self.frames;
+        self.frames += 1;
+        let midi = pitch.map(|frequency| frequency_to_midi(frequency, self.a4_hz));
+        if let Some((open_midi, _, energy)) = &mut self.current {
+            if midi == Some(*open_midi) {
+                *energy += rms * rms;
+                return None;
+            }
+        }
+        let ended = self.close(frame);
+        self.current = midi.map(|midi| (midi, frame, rms * rms));
+        ended
+    }
+
+    // End the open note, if any, after the last frame pushed
+    pub(crate) fn finish(&mut self) -> Option<NoteEvent> {
+        self.close(self.frames)
+    }
+
+    fn close(&mut self, end: usize) -> Option<NoteEvent> {
+        let frame_time = |frame: usize| (frame * self.hop) as f32 / self.sample_rate;
+        let (midi, start, energy) = self.current.take()?;
+        Some(NoteEvent {
+            midi,
+            start_sec: frame_time(start),
+            end_sec: frame_time(end),
+            rms: (energy / (end - start) as f32).sqrt(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::note;
+
+    #[test]
+    fn rounds_pitches_against_the_tuning() {
+        // A4 in baroque tuning, then silence
+        let pitches = [Some(415.0), Some(416.0), None];
+        let midis = |a4_hz: f32| -> Vec<i32> {
+            segment_notes(&pitches, &[0.5; 3], 100, 1000.0, a4_hz).iter().map(|note| note.midi).collect()
+        };
+        assert_eq!(midis(415.0), [69]);
+        assert_eq!(midis(440.0), [68]);
+    }
+
+    #[test]
+    fn drops_or_merges_short_notes() {
+        // A phantom 61 splits the 60, a short 60 trails the 60 after a silent frame, and a short
+        // 62 leads into a 62
+        let notes = [
+            note(60, 0.0, 0.5),
+            note(61, 0.5, 0.53),
+            note(60, 0.53, 1.0),
+            note(60, 1.02, 1.05),
+            note(62, 1.1, 1.13),
+            note(62, 1.13, 1.6),
+        ];
+        let spans = |notes: Vec<NoteEvent>| -> Vec<(i32, f32, f32)> {
+            notes.iter().map(|note| (note.midi, note.start_sec, note.end_sec)).collect()
+        };
+        assert_eq!(spans(drop_short_notes(&notes, 0.06, false)), [(60, 0.0, 0.5), (60, 0.53, 1.0), (62, 1.13, 1.6)]);
+        assert_eq!(spans(drop_short_notes(&notes, 0.06, true)), [(60, 0.0, 1.05), (62, 1.1, 1.6)]);
+        assert_eq!(drop_short_notes(&notes, 0.0, true), notes);
+    }
+}
@@ -0,0 +1,52 @@
+/// Smooth a pitch track with a sliding median over `window` frames centered on each frame.
+///
+/// Silent (`None`) frames stay silent and are left out of their neighbours' medians, so a
+/// single-frame octave jump between steady frames is replaced by the surrounding pitch. When the
+/// window holds an even number of voiced frames the upper of the two middle pitches is used, so
+/// the output only contains pitches present in the input. A `window` of 0 or 1 leaves the track
+/// unchanged.
+pub fn median_filter(pitches: &[Option<f32>], window: usize) -> Vec<Option<f32>> {
+    let half = window / 2;
+    let mut neighbours = Vec::with_capacity(window);
+    pitches.iter()
+        .enumerate()
+        .map(|(i, pitch)| {
+            pitch.as_ref()?;
+            let end = (i + half + 1).min(pitches.len());
+            neighbours.clear();
+            neighbours.extend(pitches[i.saturating_sub(half)..end].iter().flatten());
+            neighbours.sort_by(f32::total_cmp);
+            Some(neighbours[neighbours.len() / 2])
+        })
+        .collect()
+}
+
+/// Smooth a spectrum with a centered moving average (boxcar) over `width` bins, averaging over
+/// the bins that exist near either end. A `width` of 0 or 1 leaves it unchanged; even widths
+/// reach one bin further above each bin than below it.
+pub fn moving_average(values: &[f32], width: usize) -> Vec<f32> {
+    let below = width.saturating_sub(1) / 2;
+    let above = width.saturating_sub(1) - below;
+    (0..values.len())
+        .map(|i| {
+            let neighbours = &values[i.saturating_sub(below)..(i + above + 1).min(values.len())];
+            neighbours.iter().sum::<f32>() / neighbours.len() as f32
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn removes_a_single_frame_spike() {
+        // A steady A4 with an octave jump in one frame, then a silent frame and a new note
+        let track = [Some(440.0), Some(440.0), Some(880.0), Some(440.0), Some(440.0), None, Some(330.0)];
+        assert_eq!(
+            median_filter(&track, 3),
+            [Some(440.0), Some(440.0), Some(440.0), Some(440.0), Some(440.0), None, Some(330.0)]
+        );
+        assert_eq!(median_filter(&track, 1), track);
+    }
+}
@@ -0,0 +1,172 @@
+use crate::plan::FftCache;
+use rayon::prelude::*;
+use realfft::num_complex::Complex;
+use realfft::RealToComplex;
+use crate::{fix_octave, peak_confidence, rms, Settings, Window};
+
+/// Default STFT frame length in samples.
+pub const DEFAULT_FRAME: usize = 2048;
+
+/// Default STFT hop between frame starts in samples.
+pub const DEFAULT_HOP: usize = 512;
+
+// Frames whose estimated peak amplitude is below this (about -40 dBFS) count as silent
+const SILENCE_THRESHOLD: f32 = 0.01;
+
+/// RMS level of each frame, framed exactly like [`stft`] so the levels line up with its spectra.
+pub fn frame_rms(samples: &[f32], frame: usize, hop: usize) -> Vec<f32> {
+    if frame < 2 || hop == 0 || samples.len() < frame {
+        return Vec::new();
+    }
+    (0..=samples.len() - frame)
+        .step_by(hop)
+        .map(|start| rms(&samples[start..start + frame]))
+        .collect()
+}
+
+/// Short-time Fourier transform: the magnitude spectrum of each Hann-windowed frame.
+///
+/// Frames of `frame` samples start every `hop` samples; a trailing partial frame is dropped, so
+/// input shorter than one frame yields no frames. Each spectrum has `frame / 2 + 1` bins.
+pub fn stft(samples: &[f32], frame: usize, hop: usize) -> Vec<Vec<f32>> {
+    stft_with(samples, frame, hop, &FftCache::default())
+}
+
+// `stft`, taking the FFT plan from `plans`
+pub(crate) fn stft_with(samples: &[f32], frame: usize, hop: usize, plans: &FftCache) -> Vec<Vec<f32>> {
+    if frame < 2 || hop == 0 || samples.len() < frame {
+        return Vec::new();
+    }
+
+    // Every frame has the same length, so one plan and one window serve them all. Frames are
+    // transformed in parallel, each thread reusing its own buffers.
+    let fft = plans.forward(frame);
+    let window = hann(frame);
+    let starts: Vec<usize> = (0..=samples.len() - frame).step_by(hop).collect();
+    starts.into_par_iter()
+        .map_init(
+            || FrameBuffers::new(fft.as_ref()),
+            |buffers, start| buffers.spectrum(fft.as_ref(), &window, &samples[start..start + frame]),
+        )
+        .collect()
+}
+
+// Hann window of `frame` samples, as applied to every STFT frame
+pub(crate) fn hann(frame: usize) -> Vec<f32> {
+    (0..frame).map(|n| Window::Hann.value(n, frame)).collect()
+}
+
+// Input, output and scratch space for transforming one frame at a time
+pub(crate) struct FrameBuffers {
+    input: Vec<f32>,
+    output: Vec<Complex<f32>>,
+    scratch: Vec<Complex<f32>>,
+}
+
+impl FrameBuffers {
+    pub(crate) fn new(fft: &dyn RealToComplex<f32>) -> Self {
+        FrameBuffers {
+            input: fft.make_input_vec(),
+            output: fft.make_output_vec(),
+            scratch: fft.make_scratch_vec(),
+        }
+    }
+
+    // Magnitude spectrum of one frame after applying `window`
+    pub(crate) fn spectrum(&mut self, fft: &dyn RealToComplex<f32>, window: &[f32], frame: &[f32]) -> Vec<f32> {
+        for ((out, &sample), &w) in self.input.iter_mut().zip(frame).zip(window) {
+            *out = sample * w;
+        }
+        fft.process_with_scratch(&mut self.input, &mut self.output, &mut self.scratch)
+            .expect("FFT buffer lengths don't match the plan");
+        self.output.iter().map(|c| c.norm()).collect()
+    }
+}
+
+/// Pitch estimated for one analysis frame.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PitchEstimate {
+    /// Estimated frequency in Hz.
+    pub frequency: f32,
+    /// How much of the frame's energy sits in the chosen peak, from 0.0 to 1.0.
+    pub confidence: f32,
+}
+
+/// Estimate the pitch of each STFT frame from its strongest bin between 20 Hz and 4,000 Hz.
+///
+/// `frames` are magnitude spectra as returned by [`stft`] for a signal at `sample_rate`. Silent
+/// frames yield `None`.
+pub fn pitch_track(frames: &[Vec<f32>], sample_rate: f32) -> Vec<Option<PitchEstimate>> {
+    pitch_track_with(frames, sample_rate, &Settings::default())
+}
+
+// `pitch_track`, searching between the settings' frequencies and refining the peak as they say
+pub(crate) fn pitch_track_with(
+    frames: &[Vec<f32>],
+    sample_rate: f32,
+    settings: &Settings,
+) -> Vec<Option<PitchEstimate>> {
+    frames.par_iter().map(|magnitudes| frame_pitch_with(magnitudes, sample_rate, settings)).collect()
+}
+
+// The `pitch_track_with` estimate of a single frame
+pub(crate) fn frame_pitch_with(
+    magnitudes: &[f32],
+    sample_rate: f32,
+    settings: &Settings,
+) -> Option<PitchEstimate> {
+    // Bin spacing of a `(bins - 1) * 2`-sample frame
+    let frame = (magnitudes.len().saturating_sub(1) * 2).max(1);
+    let hz_per_bin = sample_rate / frame as f32;
+    let low = (settings.min_freq / hz_per_bin).ceil() as usize;
+    let high = ((settings.max_freq / hz_per_bin).floor() as usize).min(magnitudes.len().saturating_sub(1));
+
+    let searched = magnitudes.get(low..=high)?;
+    let (index, &peak) = searched.iter()
+        .enumerate()
+        .max_by(|a, b| a.1.total_cmp(b.1))?;
+
+    // A Hann-windowed sine of amplitude A peaks at roughly A * frame / 4
+    if peak / (frame as f32 / 4.0) < SILENCE_THRESHOLD {
+        return None;
+    }
+    let confidence = peak_confidence(searched, index);
+    let index = if settings.fix_octave { fix_octave(magnitudes, low + index, low) } else { low + index };
+    Some(PitchEstimate {
+        frequency: (index as f32 + settings.interpolation.offset(magnitudes, index)) * hz_per_bin,
+        confidence,
+    })
+}
+
+/// The frequency of each frame's [`pitch_track`] estimate, `None` for silent frames.
+pub fn frame_pitches(frames: &[Vec<f32>], sample_rate: f32) -> Vec<Option<f32>> {
+    pitch_track(frames, sample_rate)
+        .into_iter()
+        .map(|estimate| estimate.map(|estimate| estimate.frequency))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parallel_stft_matches_sequential_frames() {
+        let samples: Vec<f32> = (0..20_000)
+            .map(|n| (n as f32 * 0.031).sin() + (n as f32 * 0.0072).cos())
+            .collect();
+        let (frame, hop) = (512, 128);
+
+        let fft = FftCache::default().forward(frame);
+        let window: Vec<f32> = (0..frame).map(|n| Window::Hann.value(n, frame)).collect();
+        let mut buffers = FrameBuffers::new(fft.as_ref());
+        let sequential: Vec<Vec<f32>> = (0..=samples.len() - frame)
+            .step_by(hop)
+            .map(|start| buffers.spectrum(fft.as_ref(), &window, &samples[start..start + frame]))
+            .collect();
+
+        let parallel = stft(&samples, frame, hop);
+        assert_eq!(parallel.len(), (samples.len() - frame) / hop + 1);
+        assert_eq!(parallel, sequential);
+    }
+}
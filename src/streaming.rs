@@ -0,0 +1,386 @@
+use crate::plan::FftCache;
+use crate::segment::Segmenter;
+use crate::stft::{frame_pitch_with, hann, FrameBuffers};
+use crate::{
+    int_scale, lowpass_kernel, rms, Error, NoteEvent, Settings, A4_HZ, DEFAULT_DOWNSAMPLE, DEFAULT_FRAME,
+    DEFAULT_HOP,
+};
+use realfft::RealToComplex;
+use std::collections::VecDeque;
+use std::io::{self, Read};
+use std::sync::Arc;
+
+// Sample frames (one sample per channel) read from the stream at a time
+const BLOCK_FRAMES: usize = 4096;
+
+/// Settings for [`detect_notes_streaming`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct StreamConfig {
+    /// Integer decimation factor applied before the STFT.
+    pub downsample: usize,
+    /// STFT frame length in downsampled samples.
+    pub frame: usize,
+    /// STFT hop between frame starts in downsampled samples.
+    pub hop: usize,
+    /// Reference frequency of A4 in Hz, which the notes' MIDI numbers are rounded against.
+    pub tuning: f32,
+    /// Where each frame's pitch is searched for: only `min_freq`, `max_freq`, `interpolation` and
+    /// `fix_octave` apply, as in [`WavAnalyzer::pitch_track`](crate::WavAnalyzer::pitch_track).
+    pub settings: Settings,
+}
+
+impl Default for StreamConfig {
+    /// The defaults of [`Settings`](crate::Settings) and [`stft`](crate::stft): downsampled by 8,
+    /// with [`DEFAULT_FRAME`]-sample frames every [`DEFAULT_HOP`] samples, and A4 at 440 Hz.
+    fn default() -> Self {
+        StreamConfig {
+            downsample: DEFAULT_DOWNSAMPLE,
+            frame: DEFAULT_FRAME,
+            hop: DEFAULT_HOP,
+            tuning: A4_HZ,
+            settings: Settings::default(),
+        }
+    }
+}
+
+/// Detect the notes of a WAV stream while reading it, in memory that doesn't grow with its length.
+///
+/// The stream is read in blocks and each block is downmixed to mono, low-pass filtered and
+/// decimated, and split into STFT frames as it arrives. The notes are the same as
+/// [`segment_notes`](crate::segment_notes) finds in the frequencies of the
+/// [`WavAnalyzer::pitch_track`](crate::WavAnalyzer::pitch_track) with the config's `settings` and
+/// the [`frame_rms`](crate::frame_rms) of the whole downsampled recording, and each note is
+/// yielded as soon as the frame after it is analyzed.
+///
+/// The header is read before returning, so a stream that isn't a WAV file the loader handles
+/// fails here; read errors later on end the iterator after an `Err` item. A data chunk cut short
+/// is analyzed up to where the stream ends. A frame shorter than 2 samples or a hop of 0 yields
+/// no notes.
+pub fn detect_notes_streaming<R: Read>(reader: R, config: StreamConfig) -> Result<NoteStream<R>, Error> {
+    let header = hound::WavReader::new(LastBytes { inner: reader, last: [0; 4] })?;
+    let spec = header.spec();
+    let sample_count = header.len() as usize;
+    if sample_count == 0 {
+        return Err(Error::EmptyFile);
+    }
+    // The reader stops right after the data chunk's length field, which gives the container width
+    let stream = header.into_inner();
+    let data_len = u32::from_le_bytes(stream.last);
+    let width = data_len as usize / sample_count;
+    let encoding = Encoding::new(spec, width)?;
+
+    let factor = config.downsample.max(1);
+    let sample_rate = spec.sample_rate as f32 / factor as f32;
+    let kernel = lowpass_kernel(sample_rate / 2.0, spec.sample_rate).unwrap_or_else(|| vec![1.0]);
+    let frame = config.frame.max(2);
+    let fft = FftCache::default().forward(frame);
+    Ok(NoteStream {
+        data: stream.inner.take(data_len as u64),
+        encoding,
+        channels: (spec.channels as usize).max(1),
+        bytes: Vec::new(),
+        // The filter sees zeros before the first sample, like the offline low-pass
+        filtered: Sliding { samples: vec![0.0; kernel.len() / 2], skip: 0 },
+        kernel,
+        factor,
+        framed: Sliding { samples: Vec::with_capacity(frame), skip: 0 },
+        frame,
+        hop: config.hop,
+        window: hann(frame),
+        buffers: FrameBuffers::new(fft.as_ref()),
+        fft,
+        sample_rate,
+        segmenter: Segmenter::new(config.hop, sample_rate, config.tuning),
+        settings: config.settings,
+        notes: VecDeque::new(),
+        finished: config.frame < 2 || config.hop == 0,
+    })
+}
+
+/// Note events of a WAV stream, detected block by block as [`detect_notes_streaming`] describes.
+pub struct NoteStream<R> {
+    data: io::Take<R>,
+    encoding: Encoding,
+    channels: usize,
+    // Bytes read but not yet decoded, at most one block
+    bytes: Vec<u8>,
+    // Mono samples awaiting the low-pass filter, starting half a kernel before the next output
+    filtered: Sliding,
+    kernel: Vec<f32>,
+    factor: usize,
+    // Downsampled samples of the next STFT frame
+    framed: Sliding,
+    frame: usize,
+    hop: usize,
+    window: Vec<f32>,
+    buffers: FrameBuffers,
+    fft: Arc<dyn RealToComplex<f32>>,
+    sample_rate: f32,
+    segmenter: Segmenter,
+    settings: Settings,
+    notes: VecDeque<NoteEvent>,
+    finished: bool,
+}
+
+impl<R: Read> NoteStream<R> {
+    /// Sample rate of the downsampled signal the notes were found in.
+    pub fn sample_rate(&self) -> f32 {
+        self.sample_rate
+    }
+
+    // Read and analyze the next block, returning false once the data is exhausted
+    fn read_block(&mut self) -> io::Result<bool> {
+        let frame_bytes = self.channels * self.encoding.width();
+        let pending = self.bytes.len();
+        self.bytes.resize(BLOCK_FRAMES * frame_bytes, 0);
+        let mut filled = pending;
+        while filled < self.bytes.len() {
+            match self.data.read(&mut self.bytes[filled..]) {
+                Ok(0) => break,
+                Ok(read) => filled += read,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+                Err(err) => return Err(err),
+            }
+        }
+        if filled == pending {
+            return Ok(false);
+        }
+
+        // Decode whole sample frames and keep a trailing partial one for the next block
+        let complete = filled - filled % frame_bytes;
+        let bytes = std::mem::take(&mut self.bytes);
+        for sample_frame in bytes[..complete].chunks_exact(frame_bytes) {
+            let sum: f32 = sample_frame.chunks_exact(self.encoding.width())
+                .map(|container| self.encoding.decode(container))
+                .sum();
+            self.push_sample(sum / self.channels as f32);
+        }
+        self.bytes = bytes;
+        self.bytes.copy_within(complete..filled, 0);
+        self.bytes.truncate(filled - complete);
+        Ok(true)
+    }
+
+    // Feed one mono sample through the low-pass filter and decimator
+    fn push_sample(&mut self, sample: f32) {
+        self.filtered.push(sample);
+        if self.filtered.samples.len() == self.kernel.len() {
+            let output = self.kernel.iter().zip(&self.filtered.samples).map(|(&k, &x)| k * x).sum();
+            self.filtered.advance(self.factor);
+            self.push_downsampled(output);
+        }
+    }
+
+    // Collect one downsampled sample, analyzing the frame it completes
+    fn push_downsampled(&mut self, sample: f32) {
+        self.framed.push(sample);
+        if self.framed.samples.len() == self.frame {
+            let magnitudes = self.buffers.spectrum(self.fft.as_ref(), &self.window, &self.framed.samples);
+            let pitch = frame_pitch_with(&magnitudes, self.sample_rate, &self.settings)
+                .map(|estimate| estimate.frequency);
+            let level = rms(&self.framed.samples);
+            self.notes.extend(self.segmenter.push(pitch, level));
+            self.framed.advance(self.hop);
+        }
+    }
+
+    // Flush the filter with the zeros the offline low-pass sees after the last sample, then end
+    // the open note
+    fn finish(&mut self) {
+        for _ in 0..self.kernel.len() / 2 {
+            self.push_sample(0.0);
+        }
+        self.notes.extend(self.segmenter.finish());
+        self.finished = true;
+    }
+}
+
+impl<R: Read> Iterator for NoteStream<R> {
+    type Item = Result<NoteEvent, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(note) = self.notes.pop_front() {
+                return Some(Ok(note));
+            }
+            if self.finished {
+                return None;
+            }
+            match self.read_block() {
+                Ok(true) => {}
+                Ok(false) => self.finish(),
+                Err(err) => {
+                    self.finished = true;
+                    return Some(Err(Error::FileOpen(err)));
+                }
+            }
+        }
+    }
+}
+
+// Samples of a window sliding over a signal, which may skip past samples not yet pushed
+struct Sliding {
+    samples: Vec<f32>,
+    skip: usize,
+}
+
+impl Sliding {
+    fn push(&mut self, sample: f32) {
+        if self.skip > 0 {
+            self.skip -= 1;
+        } else {
+            self.samples.push(sample);
+        }
+    }
+
+    // Move the window start `count` samples later
+    fn advance(&mut self, count: usize) {
+        let dropped = count.min(self.samples.len());
+        self.samples.drain(..dropped);
+        self.skip += count - dropped;
+    }
+}
+
+// How each sample container of the data chunk is turned into a sample in [-1.0, 1.0]
+enum Encoding {
+    // Little-endian integers `width` bytes wide, placed in the top of an i32, shifted right by
+    // `shift` and divided by `scale`
+    Int { width: usize, shift: u32, scale: f32 },
+    // Single bytes centred on 128, as 8-bit WAV stores them
+    Unsigned8,
+    Float,
+}
+
+impl Encoding {
+    // Decode like the offline loader: valid bits narrower than the container are left-aligned.
+    // Integers come in containers of 1 to 4 bytes; any other width can't be placed in an i32
+    fn new(spec: hound::WavSpec, width: usize) -> Result<Self, Error> {
+        match spec.sample_format {
+            hound::SampleFormat::Int if !(1..=4).contains(&width) => {
+                Err(Error::UnsupportedBitDepth { bits: spec.bits_per_sample, format: "integer" })
+            }
+            hound::SampleFormat::Int if spec.bits_per_sample < width as u16 * 8 => {
+                Ok(Encoding::Int { width, shift: 0, scale: (1u64 << 31) as f32 })
+            }
+            hound::SampleFormat::Int if spec.bits_per_sample == 8 && width == 1 => Ok(Encoding::Unsigned8),
+            hound::SampleFormat::Int => {
+                let bits = spec.bits_per_sample;
+                let scale = int_scale(bits).ok_or(Error::UnsupportedBitDepth { bits, format: "integer" })?;
+                Ok(Encoding::Int { width, shift: 32 - 8 * width as u32, scale })
+            }
+            hound::SampleFormat::Float if spec.bits_per_sample == 32 => Ok(Encoding::Float),
+            hound::SampleFormat::Float => {
+                Err(Error::UnsupportedBitDepth { bits: spec.bits_per_sample, format: "float" })
+            }
+        }
+    }
+
+    fn width(&self) -> usize {
+        match self {
+            Encoding::Int { width, .. } => *width,
+            Encoding::Unsigned8 => 1,
+            Encoding::Float => 4,
+        }
+    }
+
+    fn decode(&self, container: &[u8]) -> f32 {
+        match *self {
+            Encoding::Int { width, shift, scale } => {
+                let mut word = [0u8; 4];
+                word[4 - width..].copy_from_slice(container);
+                (i32::from_le_bytes(word) >> shift) as f32 / scale
+            }
+            Encoding::Unsigned8 => (container[0] as f32 - 128.0) / 128.0,
+            Encoding::Float => f32::from_le_bytes(container.try_into().expect("float containers are 4 bytes")),
+        }
+    }
+}
+
+// Reader that remembers the last four bytes read through it
+struct LastBytes<R> {
+    inner: R,
+    last: [u8; 4],
+}
+
+impl<R: Read> Read for LastBytes<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        let tail = &buf[read.saturating_sub(4)..read];
+        self.last.rotate_left(tail.len());
+        self.last[4 - tail.len()..].copy_from_slice(tail);
+        Ok(read)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{mix, wav};
+    use crate::{downsample, frame_pitches, frame_rms, segment_notes, stft};
+    use std::io::Cursor;
+
+    #[test]
+    fn streaming_matches_offline_segmentation() {
+        // A stereo melody of three notes with a pause, at 16-bit
+        let sample_rate = 44_100;
+        let tones = [(440.0, 0.6), (0.0, 0.2), (523.25, 0.5), (659.25, 0.7)];
+        let mut mono = Vec::new();
+        for (frequency, seconds) in tones {
+            mono.extend(mix(&[(frequency, 0.5)], (seconds * sample_rate as f32) as usize, sample_rate));
+        }
+        let stereo: Vec<f32> = mono.iter().flat_map(|&sample| [sample, sample]).collect();
+        let bytes = wav(&stereo, 2, sample_rate, 16);
+
+        let config = StreamConfig::default();
+        let audio = crate::read_wav_from(Cursor::new(&bytes)).unwrap();
+        let (samples, rate) = downsample(&audio.mono(), audio.sample_rate, config.downsample);
+        let pitches = frame_pitches(&stft(&samples, config.frame, config.hop), rate);
+        let levels = frame_rms(&samples, config.frame, config.hop);
+        let offline = segment_notes(&pitches, &levels, config.hop, rate, config.tuning);
+
+        let streamed: Vec<NoteEvent> = detect_notes_streaming(Cursor::new(&bytes), config.clone())
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(streamed, offline);
+        assert!(streamed.iter().any(|note| note.midi == 69) && streamed.iter().any(|note| note.midi == 76));
+    }
+
+    #[test]
+    fn searches_each_frame_between_the_settings_frequencies() {
+        // C4, E4 and G4 for half a second each, over a louder 50 Hz hum
+        let sample_rate = 8000;
+        let half = sample_rate as usize / 2;
+        let hum = mix(&[(50.0, 1.0)], 3 * half, sample_rate);
+        let melody = [261.63, 329.63, 392.0]
+            .iter()
+            .flat_map(|&frequency| mix(&[(frequency, 0.3)], half, sample_rate));
+        let samples: Vec<f32> = melody.zip(&hum).map(|(note, hum)| note + 0.5 * hum).collect();
+        let bytes = wav(&samples, 1, sample_rate, 16);
+        let config = StreamConfig { downsample: 1, frame: 1024, hop: 256, ..StreamConfig::default() };
+        let midis = |config: StreamConfig| -> Vec<i32> {
+            detect_notes_streaming(Cursor::new(&bytes), config).unwrap().map(|note| note.unwrap().midi).collect()
+        };
+        assert!(midis(config.clone()).iter().all(|&midi| midi < 40));
+
+        let settings = Settings { min_freq: 100.0, ..Settings::default() };
+        assert_eq!(midis(StreamConfig { settings, ..config }), [60, 64, 67]);
+    }
+
+    #[test]
+    fn rejects_integer_containers_outside_1_to_4_bytes() {
+        let spec = hound::WavSpec {
+            channels: 1,
+            sample_rate: 8000,
+            bits_per_sample: 24,
+            sample_format: hound::SampleFormat::Int,
+        };
+        for width in [0, 5, 8] {
+            assert!(matches!(
+                Encoding::new(spec, width),
+                Err(Error::UnsupportedBitDepth { bits: 24, format: "integer" })
+            ));
+        }
+        assert!(matches!(Encoding::new(spec, 3), Ok(Encoding::Int { width: 3, shift: 8, .. })));
+    }
+}
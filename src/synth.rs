@@ -0,0 +1,99 @@
+use crate::{midi_to_frequency, NoteEvent};
+use std::f32::consts::{PI, SQRT_2};
+use std::io;
+use std::path::Path;
+
+// Length of the fade at each end of a rendered tone, so notes start and stop without clicks
+const FADE_SEC: f32 = 0.005;
+
+/// A sine at `frequency` Hz lasting `seconds`, with the given RMS level, faded in and out over
+/// 5 ms.
+pub fn sine(frequency: f32, seconds: f32, rms: f32, sample_rate: u32) -> Vec<f32> {
+    let len = (seconds.max(0.0) * sample_rate as f32).round() as usize;
+    let fade = ((FADE_SEC * sample_rate as f32) as usize).clamp(1, len.div_ceil(2).max(1));
+    let amplitude = (rms * SQRT_2).min(1.0);
+    (0..len)
+        .map(|n| {
+            let envelope = (n.min(len - 1 - n) as f32 / fade as f32).min(1.0);
+            amplitude * envelope * (2.0 * PI * frequency * n as f32 / sample_rate as f32).sin()
+        })
+        .collect()
+}
+
+/// Render note events as a mono [`sine`] per note at its RMS level, with A4 tuned to `a4_hz`.
+///
+/// Each note starts at its own onset, with silence in the gaps between notes; the result lasts
+/// until the last note ends.
+pub fn render_notes(notes: &[NoteEvent], a4_hz: f32, sample_rate: u32) -> Vec<f32> {
+    let to_index = |seconds: f32| (seconds.max(0.0) * sample_rate as f32).round() as usize;
+    let mut samples = vec![0.0; notes.iter().map(|note| to_index(note.end_sec)).max().unwrap_or(0)];
+    for note in notes {
+        let tone = sine(midi_to_frequency(note.midi, a4_hz), note.duration(), note.rms, sample_rate);
+        let start = to_index(note.start_sec).min(samples.len());
+        for (out, sample) in samples[start..].iter_mut().zip(tone) {
+            *out += sample;
+        }
+    }
+    samples
+}
+
+/// Write mono samples in `[-1.0, 1.0]` to a 16-bit WAV file; samples outside that range are
+/// clipped.
+pub fn write_wav(samples: &[f32], sample_rate: u32, path: &Path) -> io::Result<()> {
+    let spec = hound::WavSpec {
+        channels: 1,
+        sample_rate,
+        bits_per_sample: 16,
+        sample_format: hound::SampleFormat::Int,
+    };
+    let mut writer = hound::WavWriter::create(path, spec).map_err(into_io)?;
+    for &sample in samples {
+        writer.write_sample((sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16).map_err(into_io)?;
+    }
+    writer.finalize().map_err(into_io)
+}
+
+// Writing fails only on I/O, so report hound's errors as the I/O errors they wrap
+fn into_io(err: hound::Error) -> io::Error {
+    match err {
+        hound::Error::IoError(err) => err,
+        err => io::Error::other(err),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::note;
+    use crate::{analyze, read_wav, Settings, A4_HZ};
+
+    #[test]
+    fn renders_each_note_at_its_own_time_and_pitch() {
+        // A4 for half a second, a quarter of a second of silence, then C5
+        let samples = render_notes(&[note(69, 0.0, 0.5), note(72, 0.75, 1.0)], A4_HZ, 8000);
+        assert_eq!(samples.len(), 8000);
+        assert!(samples[4000..6000].iter().all(|&sample| sample == 0.0));
+        let settings = Settings { downsample: 1, ..Settings::default() };
+        let pitch = |samples: &[f32]| analyze(samples, 8000, &settings).unwrap().frequency.unwrap();
+        assert!((pitch(&samples[..4000]) - 440.0).abs() < 1.0);
+        assert!((pitch(&samples[6000..]) - 523.25).abs() < 1.0);
+        assert!(render_notes(&[], A4_HZ, 8000).is_empty());
+    }
+
+    #[test]
+    fn writes_16_bit_wav_that_reads_back() {
+        let path = std::env::temp_dir().join(format!("wav2note-synth-{}.wav", std::process::id()));
+        let mut samples = sine(440.0, 0.5, 0.5, 8000);
+        samples.push(1.5);
+        write_wav(&samples, 8000, &path).unwrap();
+        let audio = read_wav(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!((audio.channels, audio.sample_rate, audio.samples.len()), (1, 8000, 4001));
+        for (read, written) in audio.samples.iter().zip(&samples[..4000]) {
+            assert!((read - written).abs() <= 1.0 / i16::MAX as f32);
+        }
+        // Clipped to full scale
+        assert_eq!(audio.samples[4000], 1.0);
+    }
+}
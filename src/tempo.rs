@@ -0,0 +1,92 @@
+use crate::parabolic_offset;
+
+/// STFT frame length in samples for the onset envelope behind [`estimate_bpm`], short enough to
+/// place onsets within a few milliseconds.
+pub const TEMPO_FRAME: usize = 512;
+
+/// STFT hop in samples for the onset envelope behind [`estimate_bpm`].
+pub const TEMPO_HOP: usize = 128;
+
+// Range of tempos searched, in beats per minute
+const MIN_BPM: f32 = 60.0;
+const MAX_BPM: f32 = 200.0;
+
+/// Estimate the tempo in beats per minute from an onset strength envelope, such as the
+/// [`spectral_flux`](crate::spectral_flux) of a spectrogram, with frames `hop` samples apart at
+/// `sample_rate`.
+///
+/// The envelope's autocorrelation peaks at the lags between recurring onsets; the strongest lag
+/// whose tempo lies between 60 and 200 BPM is the beat, taking the faster of two tempos an octave
+/// apart when their peaks are about as strong. Returns 0.0 when the envelope is too
+/// short to hold two beats at 60 BPM or has no onsets.
+pub fn estimate_bpm(onset_env: &[f32], hop: usize, sample_rate: f32) -> f32 {
+    let frame_rate = sample_rate / hop as f32;
+    let min_lag = ((60.0 * frame_rate / MAX_BPM).floor() as usize).max(1);
+    let max_lag = (60.0 * frame_rate / MIN_BPM).ceil() as usize;
+    if onset_env.len() <= max_lag + 1 {
+        return 0.0;
+    }
+
+    // Spread each onset over its neighbouring frames, so a beat period falling between two lags
+    // still gives a peak at one of them
+    let mut smoothed = onset_env.to_vec();
+    for i in 1..onset_env.len() - 1 {
+        smoothed[i] = 0.25 * onset_env[i - 1] + 0.5 * onset_env[i] + 0.25 * onset_env[i + 1];
+    }
+    let mean = smoothed.iter().sum::<f32>() / smoothed.len() as f32;
+    let centered: Vec<f32> = smoothed.iter().map(|&x| x - mean).collect();
+    let autocorrelation: Vec<f32> = (0..=max_lag + 1)
+        .map(|lag| centered.iter().zip(&centered[lag..]).map(|(a, b)| a * b).sum())
+        .collect();
+
+    let strongest = autocorrelation[min_lag..=max_lag].iter().cloned().fold(f32::MIN, f32::max);
+    if strongest <= 0.0 {
+        return 0.0;
+    }
+    // Every multiple of the beat period recurs as well, and one can edge out the period itself
+    // when the period falls between lags; take the first local maximum close to the strongest
+    let Some(best) = (min_lag..=max_lag).find(|&lag| {
+        let value = autocorrelation[lag];
+        value >= 0.8 * strongest && value >= autocorrelation[lag - 1] && value >= autocorrelation[lag + 1]
+    }) else {
+        return 0.0;
+    };
+    let offset = parabolic_offset(autocorrelation[best - 1], autocorrelation[best], autocorrelation[best + 1]);
+    60.0 * frame_rate / (best as f32 + offset)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{spectral_flux, stft};
+    use std::f32::consts::PI;
+
+    // A metronome: a 10 ms decaying 1 kHz click on every beat
+    fn click_track(bpm: f32, seconds: f32, sample_rate: u32) -> Vec<f32> {
+        let beat = (60.0 / bpm * sample_rate as f32) as usize;
+        let click = (0.01 * sample_rate as f32) as usize;
+        (0..(seconds * sample_rate as f32) as usize)
+            .map(|n| {
+                let since = n % beat;
+                let t = since as f32 / sample_rate as f32;
+                if since < click { (-t * 400.0).exp() * (2.0 * PI * 1000.0 * t).sin() } else { 0.0 }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn finds_the_tempo_of_a_click_track() {
+        let sample_rate = 8000;
+        for bpm in [100.0, 72.0, 160.0] {
+            let samples = click_track(bpm, 10.0, sample_rate);
+            let flux = spectral_flux(&stft(&samples, TEMPO_FRAME, TEMPO_HOP));
+            let estimate = estimate_bpm(&flux, TEMPO_HOP, sample_rate as f32);
+            assert!((estimate - bpm).abs() < 1.5, "expected {bpm} BPM, got {estimate}");
+        }
+    }
+
+    #[test]
+    fn silence_has_no_tempo() {
+        assert_eq!(estimate_bpm(&[0.0; 1000], TEMPO_HOP, 8000.0), 0.0);
+    }
+}
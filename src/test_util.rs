@@ -0,0 +1,69 @@
+// Signals, note events and WAV files shared by the unit tests
+use crate::NoteEvent;
+use std::f64::consts::TAU;
+use std::io::Cursor;
+
+// A note event at a moderate level
+pub(crate) fn note(midi: i32, start_sec: f32, end_sec: f32) -> NoteEvent {
+    NoteEvent { midi, start_sec, end_sec, rms: 0.3 }
+}
+
+// `len` samples of a unit-amplitude sine at `frequency` Hz, its phase computed in f64 so long
+// tones stay exact
+pub(crate) fn sine(frequency: f32, len: usize, sample_rate: u32) -> Vec<f32> {
+    mix(&[(frequency, 1.0)], len, sample_rate)
+}
+
+// `len` samples of sines summed, given as (frequency in Hz, amplitude)
+pub(crate) fn mix(partials: &[(f32, f32)], len: usize, sample_rate: u32) -> Vec<f32> {
+    (0..len)
+        .map(|n| {
+            let t = n as f64 / sample_rate as f64;
+            partials.iter()
+                .map(|&(frequency, amplitude)| amplitude as f64 * (TAU * frequency as f64 * t).sin())
+                .sum::<f64>() as f32
+        })
+        .collect()
+}
+
+// Uniform white noise in [-0.5, 0.5] from a linear congruential generator, the same on every run
+pub(crate) struct Noise(u32);
+
+impl Noise {
+    pub(crate) fn new() -> Self {
+        Noise(1)
+    }
+
+    pub(crate) fn next(&mut self) -> f32 {
+        self.0 = self.0.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+        self.0 as f32 / u32::MAX as f32 - 0.5
+    }
+}
+
+// A WAV file of `samples` interleaved over `channels`, as integers of `bits` bits scaled so 1.0 is
+// the largest positive value
+pub(crate) fn wav(samples: &[f32], channels: u16, sample_rate: u32, bits: u16) -> Vec<u8> {
+    let spec =
+        hound::WavSpec { channels, sample_rate, bits_per_sample: bits, sample_format: hound::SampleFormat::Int };
+    let scale = ((1i64 << (bits - 1)) - 1) as f32;
+    let mut bytes = Cursor::new(Vec::new());
+    let mut writer = hound::WavWriter::new(&mut bytes, spec).unwrap();
+    for &sample in samples {
+        writer.write_sample((sample * scale).round() as i32).unwrap();
+    }
+    writer.finalize().unwrap();
+    bytes.into_inner()
+}
+
+// A WAV file of `samples` interleaved over `channels`, as 32-bit floats
+pub(crate) fn float_wav(samples: &[f32], channels: u16, sample_rate: u32) -> Vec<u8> {
+    let spec =
+        hound::WavSpec { channels, sample_rate, bits_per_sample: 32, sample_format: hound::SampleFormat::Float };
+    let mut bytes = Cursor::new(Vec::new());
+    let mut writer = hound::WavWriter::new(&mut bytes, spec).unwrap();
+    for &sample in samples {
+        writer.write_sample(sample).unwrap();
+    }
+    writer.finalize().unwrap();
+    bytes.into_inner()
+}
@@ -0,0 +1,98 @@
+use crate::{apply_window, frequency_to_midi, midi_to_frequency, parabolic_offset, Window};
+
+/// Power of `samples` at `target_hz`, computed with the Goertzel algorithm.
+///
+/// Equivalent to the squared magnitude of a single DFT bin, but the target doesn't have to fall on
+/// a bin and the cost is one pass over the samples.
+pub fn goertzel(samples: &[f32], target_hz: f32, sample_rate: f32) -> f32 {
+    let coeff = 2.0 * (2.0 * std::f32::consts::PI * target_hz / sample_rate).cos();
+    let (mut s1, mut s2) = (0.0f32, 0.0f32);
+    for &sample in samples {
+        let s0 = sample + coeff * s1 - s2;
+        s2 = s1;
+        s1 = s0;
+    }
+    s1 * s1 + s2 * s2 - coeff * s1 * s2
+}
+
+/// Fundamental frequency from a handful of [`goertzel`] evaluations instead of a full FFT.
+///
+/// A zero-crossing count gives a rough pitch; the twelve notes around it (A4 tuned to `a4_hz`)
+/// are compared and the strongest wins. The frequency is then refined within half a semitone of
+/// that note. Returns `None` for silence or a signal without zero crossings.
+pub fn tuner_pitch(samples: &[f32], sample_rate: f32, a4_hz: f32) -> Option<f32> {
+    if samples.len() < 2 {
+        return None;
+    }
+    let crossings = samples.windows(2).filter(|pair| (pair[0] < 0.0) != (pair[1] < 0.0)).count();
+    if crossings == 0 {
+        return None;
+    }
+    let rough_hz = crossings as f32 * sample_rate / (2.0 * samples.len() as f32);
+
+    // A Hann window keeps the sidelobes of one note from outweighing its neighbours
+    let (windowed, _) = apply_window(samples, Window::Hann);
+    let power = |hz: f32| goertzel(&windowed, hz, sample_rate);
+
+    let nyquist = sample_rate / 2.0;
+    let rough_midi = frequency_to_midi(rough_hz, a4_hz);
+    let best_midi = (rough_midi - 6..rough_midi + 6)
+        .map(|midi| (midi, midi_to_frequency(midi, a4_hz)))
+        .filter(|&(_, hz)| hz < nyquist)
+        .max_by(|a, b| power(a.1).total_cmp(&power(b.1)))?
+        .0;
+    let note_hz = midi_to_frequency(best_midi, a4_hz);
+    if power(note_hz) == 0.0 {
+        return None;
+    }
+
+    // Step through the ±50 cents around the note at half the buffer's frequency resolution, then
+    // fit a parabola through the strongest step and its neighbours
+    let resolution_hz = sample_rate / samples.len() as f32;
+    let step_cents = (1200.0 * (1.0 + resolution_hz / (2.0 * note_hz)).log2()).max(1.0);
+    let steps = (50.0 / step_cents).ceil() as i32;
+    let at_cents = |cents: f32| note_hz * 2f32.powf(cents / 1200.0);
+    let log_power = |step: i32| power(at_cents(step as f32 * step_cents)).max(f32::MIN_POSITIVE).ln();
+    let best_step = (-steps..=steps)
+        .map(|step| (step, log_power(step)))
+        .max_by(|a, b| a.1.total_cmp(&b.1))?
+        .0;
+    let offset = parabolic_offset(log_power(best_step - 1), log_power(best_step), log_power(best_step + 1));
+    Some(at_cents((best_step as f32 + offset) * step_cents))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::sine;
+    use std::f64::consts::TAU;
+
+    #[test]
+    fn goertzel_matches_the_dft_bin() {
+        // 1 kHz is bin 100 of an 800-point DFT at 8 kHz
+        let samples = sine(1000.0, 800, 8000);
+        let (re, im) = samples.iter().enumerate().fold((0.0, 0.0), |(re, im), (n, &sample)| {
+            let phase = TAU * 100.0 * n as f64 / 800.0;
+            (re + sample as f64 * phase.cos(), im - sample as f64 * phase.sin())
+        });
+        let bin_power = (re * re + im * im) as f32;
+        let power = goertzel(&samples, 1000.0, 8000.0);
+        assert!((power - bin_power).abs() / bin_power < 1e-3, "{power} against {bin_power}");
+    }
+
+    #[test]
+    fn reads_a_tone_ten_cents_sharp() {
+        // 10 cents above 440 Hz is 442.55 Hz
+        let sharp = 440.0 * 2f32.powf(10.0 / 1200.0);
+        let pitch = tuner_pitch(&sine(sharp, 4000, 8000), 8000.0, 440.0).unwrap();
+        let cents = 1200.0 * (pitch / 440.0).log2();
+        assert!((cents - 10.0).abs() < 1.0, "{pitch} Hz, {cents} cents");
+    }
+
+    #[test]
+    fn silence_and_single_samples_have_no_pitch() {
+        assert_eq!(tuner_pitch(&[0.0; 4000], 8000.0, 440.0), None);
+        assert_eq!(tuner_pitch(&[0.5], 8000.0, 440.0), None);
+        assert_eq!(tuner_pitch(&[], 8000.0, 440.0), None);
+    }
+}
@@ -0,0 +1,139 @@
+use crate::{frame_pitches, interpolate_peak, stft, NoteEvent, Window};
+use realfft::RealFftPlanner;
+use serde::Serialize;
+
+/// STFT frame length in samples for [`note_vibrato`]'s pitch track, short enough to follow the
+/// pitch through each vibrato cycle.
+pub const VIBRATO_FRAME: usize = 256;
+
+/// STFT hop in samples for [`note_vibrato`]'s pitch track.
+pub const VIBRATO_HOP: usize = 32;
+
+// Range of oscillation rates that count as vibrato, in Hz
+const MIN_RATE_HZ: f32 = 3.0;
+const MAX_RATE_HZ: f32 = 12.0;
+
+// Shallower oscillations are indistinguishable from pitch-tracking noise
+const MIN_DEPTH_CENTS: f32 = 5.0;
+
+// Fraction of the pitch variation's energy that must lie in the vibrato peak
+const MIN_PEAK_ENERGY: f32 = 0.5;
+
+/// Periodic oscillation of a sustained note's pitch.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Vibrato {
+    /// Oscillations per second.
+    pub rate_hz: f32,
+    /// Amplitude of the oscillation in cents; the pitch swings this far above and below its
+    /// center.
+    pub depth_cents: f32,
+}
+
+/// Measure the vibrato in the pitch track of one sustained note, `frame_rate` frames per second.
+///
+/// The pitches are converted to cents, detrended to remove slow drift, and windowed; the
+/// strongest component of their spectrum between 3 Hz and 12 Hz gives the rate and depth. Silent
+/// frames at either end are ignored. Returns `None` when there's no clear vibrato: for a track
+/// shorter than two cycles at 3 Hz, with silent frames inside it, whose oscillation is shallower
+/// than 5 cents, or whose variation isn't mostly at a single rate.
+pub fn vibrato(pitches: &[Option<f32>], frame_rate: f32) -> Option<Vibrato> {
+    let first = pitches.iter().position(Option::is_some)?;
+    let last = pitches.iter().rposition(Option::is_some)?;
+    let voiced: Vec<f32> = pitches[first..=last].iter().copied().collect::<Option<_>>()?;
+    let len = voiced.len();
+    if (len as f32) < 2.0 * frame_rate / MIN_RATE_HZ || voiced.iter().any(|&pitch| pitch <= 0.0) {
+        return None;
+    }
+
+    // Cents relative to the first frame, minus the least-squares line through them
+    let cents: Vec<f32> = voiced.iter().map(|&pitch| 1200.0 * (pitch / voiced[0]).log2()).collect();
+    let mean_x = (len - 1) as f32 / 2.0;
+    let mean_y = cents.iter().sum::<f32>() / len as f32;
+    let covariance: f32 = cents.iter().enumerate().map(|(x, &y)| (x as f32 - mean_x) * (y - mean_y)).sum();
+    let variance: f32 = (0..len).map(|x| (x as f32 - mean_x).powi(2)).sum();
+    let slope = covariance / variance;
+
+    // Zero-pad at least twofold so the peak falls close to a bin
+    let size = (2 * len).next_power_of_two();
+    let window: Vec<f32> = (0..len).map(|n| Window::Hann.value(n, len)).collect();
+    let mut buffer: Vec<f32> = cents.iter()
+        .zip(&window)
+        .enumerate()
+        .map(|(x, (&y, &w))| (y - mean_y - slope * (x as f32 - mean_x)) * w)
+        .collect();
+    buffer.resize(size, 0.0);
+    let fft = RealFftPlanner::<f32>::new().plan_fft_forward(size);
+    let mut spectrum = fft.make_output_vec();
+    fft.process(&mut buffer, &mut spectrum).expect("FFT buffer lengths don't match the plan");
+    let magnitudes: Vec<f32> = spectrum.iter().map(|c| c.norm()).collect();
+
+    let hz_per_bin = frame_rate / size as f32;
+    let low = (MIN_RATE_HZ / hz_per_bin).ceil() as usize;
+    let high = ((MAX_RATE_HZ / hz_per_bin).floor() as usize).min(magnitudes.len() - 2);
+    let peak = (low..=high).max_by(|&a, &b| magnitudes[a].total_cmp(&magnitudes[b]))?;
+
+    // The Hann main lobe spans two bins of the unpadded track on each side
+    let lobe = (2 * size).div_ceil(len);
+    let energy = |bins: &[f32]| bins.iter().map(|m| m * m).sum::<f32>();
+    let lobe_energy = energy(&magnitudes[peak.saturating_sub(lobe)..(peak + lobe + 1).min(magnitudes.len())]);
+    if lobe_energy < MIN_PEAK_ENERGY * energy(&magnitudes) {
+        return None;
+    }
+
+    // A windowed sinusoid of amplitude A peaks at A times half the window's sum
+    let depth_cents = 2.0 * magnitudes[peak] / window.iter().sum::<f32>();
+    if depth_cents < MIN_DEPTH_CENTS {
+        return None;
+    }
+    Some(Vibrato { rate_hz: (peak as f32 + interpolate_peak(&magnitudes, peak)) * hz_per_bin, depth_cents })
+}
+
+/// Measure the [`vibrato`] of a note found in mono `samples` at `sample_rate`, from a pitch track
+/// of [`VIBRATO_FRAME`]-sample frames every [`VIBRATO_HOP`] samples over the note's span.
+pub fn note_vibrato(samples: &[f32], sample_rate: f32, note: &NoteEvent) -> Option<Vibrato> {
+    let to_index = |seconds: f32| ((seconds.max(0.0) * sample_rate) as usize).min(samples.len());
+    let span = &samples[to_index(note.start_sec)..to_index(note.end_sec)];
+    let pitches = frame_pitches(&stft(span, VIBRATO_FRAME, VIBRATO_HOP), sample_rate);
+    vibrato(&pitches, sample_rate / VIBRATO_HOP as f32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::PI;
+
+    // A sine around `frequency` whose pitch swings `depth_cents` at `rate_hz`
+    fn vibrato_tone(frequency: f32, rate_hz: f32, depth_cents: f32, seconds: f32, sample_rate: u32) -> Vec<f32> {
+        let mut phase = 0.0;
+        (0..(seconds * sample_rate as f32) as usize)
+            .map(|n| {
+                let t = n as f32 / sample_rate as f32;
+                let cents = depth_cents * (2.0 * PI * rate_hz * t).sin();
+                phase += 2.0 * PI * frequency * 2f32.powf(cents / 1200.0) / sample_rate as f32;
+                0.5 * phase.sin()
+            })
+            .collect()
+    }
+
+    fn whole(samples: &[f32], sample_rate: u32) -> NoteEvent {
+        NoteEvent { midi: 69, start_sec: 0.0, end_sec: samples.len() as f32 / sample_rate as f32, rms: 0.35 }
+    }
+
+    #[test]
+    fn measures_rate_and_depth() {
+        let sample_rate = 8000;
+        let samples = vibrato_tone(440.0, 5.8, 28.0, 1.5, sample_rate);
+        let vibrato = note_vibrato(&samples, sample_rate as f32, &whole(&samples, sample_rate)).unwrap();
+        assert!((vibrato.rate_hz - 5.8).abs() < 0.3, "rate {}", vibrato.rate_hz);
+        assert!((vibrato.depth_cents - 28.0).abs() < 5.0, "depth {}", vibrato.depth_cents);
+    }
+
+    #[test]
+    fn steady_and_short_notes_have_no_vibrato() {
+        let sample_rate = 8000;
+        let steady = vibrato_tone(440.0, 5.8, 0.0, 1.5, sample_rate);
+        assert_eq!(note_vibrato(&steady, sample_rate as f32, &whole(&steady, sample_rate)), None);
+        let short = vibrato_tone(440.0, 5.8, 28.0, 0.4, sample_rate);
+        assert_eq!(note_vibrato(&short, sample_rate as f32, &whole(&short, sample_rate)), None);
+    }
+}
@@ -0,0 +1,52 @@
+// The command line as a user runs it, on WAV streams piped to its stdin
+use std::io::{Cursor, Write};
+use std::process::{Command, Stdio};
+
+// Two seconds of C4 as a 16-bit mono WAV file at 44.1 kHz
+fn c4() -> Vec<u8> {
+    let spec = hound::WavSpec {
+        channels: 1,
+        sample_rate: 44_100,
+        bits_per_sample: 16,
+        sample_format: hound::SampleFormat::Int,
+    };
+    let mut wav = Cursor::new(Vec::new());
+    let mut writer = hound::WavWriter::new(&mut wav, spec).unwrap();
+    for sample in helloworld::sine(261.63, 2.0, 0.5, 44_100) {
+        writer.write_sample((sample * i16::MAX as f32) as i16).unwrap();
+    }
+    writer.finalize().unwrap();
+    wav.into_inner()
+}
+
+// Stdout of the program run with `args` on `wav`, which it must exit successfully from
+fn run(args: &[&str], wav: &[u8]) -> String {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_helloworld"))
+        .args(args)
+        .arg("-")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(wav).unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn octave_offset_renames_only_the_text_output() {
+    let wav = c4();
+    let text = run(&["--octave-offset", "1"], &wav);
+    assert!(text.contains("Closest musical note: C5 (60)"), "{text}");
+    assert!(text.lines().any(|line| line.trim_start().starts_with("C5   ( 60) at")), "{text}");
+
+    // The machine-readable formats keep the standard names
+    let json = run(&["--octave-offset", "1", "--json"], &wav);
+    assert!(json.contains("\"note\": \"C4\""), "{json}");
+    assert!(!json.contains("C5"), "{json}");
+    let csv = run(&["--octave-offset", "1", "--format", "csv"], &wav);
+    assert!(csv.contains(",60,C4,"), "{csv}");
+    assert!(!csv.contains("C5"), "{csv}");
+}
@@ -0,0 +1,78 @@
+// The library only computes: running its analyses must leave stdout and stderr untouched, which
+// only a separate process can observe, so the check runs this test binary again on its own
+use helloworld::{StreamConfig, WavAnalyzer};
+use std::env;
+use std::io::Cursor;
+use std::process::Command;
+
+// Set in the child process, which then runs the analyses instead of skipping them
+const CHILD: &str = "WAV2NOTE_SILENT_CHILD";
+
+fn melody(sample_rate: u32) -> Vec<f32> {
+    [261.63, 329.63, 392.0]
+        .iter()
+        .flat_map(|&frequency| helloworld::sine(frequency, 0.5, 0.3, sample_rate))
+        .collect()
+}
+
+#[test]
+fn run_the_analyses() {
+    if env::var_os(CHILD).is_none() {
+        return;
+    }
+    let sample_rate = 44_100;
+    let samples = melody(sample_rate);
+    let analyzer = WavAnalyzer::new().precise(true);
+    let transcription = analyzer.transcribe(&samples, sample_rate, 1024, 256).unwrap();
+    assert!(!transcription.notes.is_empty());
+    let analysis = analyzer.analyze(&samples, sample_rate).unwrap();
+    analysis.note(analyzer.tuning, analyzer.notation).unwrap();
+    analysis.top_peaks(5, analyzer.tuning, analyzer.notation);
+    assert!(analyzer.analyze(&samples[..10], sample_rate).is_err());
+
+    // The streaming detector and the exporters, which write only where they're told to
+    let mut wav = Cursor::new(Vec::new());
+    let spec = hound::WavSpec {
+        channels: 1,
+        sample_rate,
+        bits_per_sample: 16,
+        sample_format: hound::SampleFormat::Int,
+    };
+    let mut writer = hound::WavWriter::new(&mut wav, spec).unwrap();
+    for &sample in &samples {
+        writer.write_sample((sample * i16::MAX as f32) as i16).unwrap();
+    }
+    writer.finalize().unwrap();
+    let streamed = helloworld::detect_notes_streaming(Cursor::new(wav.into_inner()), StreamConfig::default());
+    assert!(streamed.unwrap().count() > 0);
+    let notes = &transcription.notes;
+    let mut midi = Vec::new();
+    helloworld::write_midi_to(notes, helloworld::VelocityCurve::default(), "Melody", &mut midi).unwrap();
+    helloworld::to_abc(notes, 120.0, "Melody");
+    helloworld::to_musicxml(notes, 120.0, helloworld::DEFAULT_GRID, "Melody");
+    helloworld::to_lilypond(notes, 120.0, helloworld::DEFAULT_GRID, "Melody");
+}
+
+#[test]
+fn analyses_write_nothing_to_stdout_or_stderr() {
+    let output = Command::new(env::current_exe().unwrap())
+        .args(["run_the_analyses", "--exact", "--nocapture", "--test-threads=1"])
+        .env(CHILD, "1")
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "{stdout}");
+    // Only the test harness's own lines; anything the library printed would stand between them,
+    // or on the line of the test itself
+    let foreign: Vec<&str> = stdout
+        .lines()
+        .filter(|line| {
+            !(line.is_empty()
+                || *line == "running 1 test"
+                || *line == "test run_the_analyses ... ok"
+                || line.starts_with("test result: ok."))
+        })
+        .collect();
+    assert!(foreign.is_empty(), "printed to stdout: {foreign:?}");
+    assert!(output.stderr.is_empty(), "printed to stderr: {}", String::from_utf8_lossy(&output.stderr));
+}